@@ -0,0 +1,207 @@
+use image::RgbaImage;
+
+use crate::images::Background;
+
+pub const WIDTH: u32 = 128;
+pub const HEIGHT: u32 = WIDTH / 4;
+
+/// Decodes and shrinks the image to one pixel per character.
+pub fn decode_image(bytes: &[u8]) -> Result<RgbaImage, String> {
+    let reader = image::io::Reader::new(std::io::Cursor::new(bytes))
+        .with_guessed_format()
+        .expect("Cursor never fails");
+    Ok(reader
+        .decode()
+        .map_err(|e| format!("Could not decode image: {}", e))?
+        .resize_to_fill(WIDTH, HEIGHT * 2, image::imageops::FilterType::Nearest)
+        .resize_exact(WIDTH, HEIGHT, image::imageops::FilterType::Nearest)
+        .to_rgba8())
+}
+
+pub fn convert_image_to_ascii(bg: &Background, image: &RgbaImage, alpha: f32) -> String {
+    image
+        .pixels()
+        .map(|p| pixel_to_intensity(bg, p))
+        .map(|i| (i as f32 * alpha) as u8)
+        .map(|i| intensity_to_ascii(i))
+        .collect::<Vec<_>>()
+        .chunks(WIDTH as usize)
+        .map(|c| c.iter().cloned().collect::<String>() + "\n")
+        .collect()
+}
+
+/// Levels kept of every colour channel in the colour mode, few enough that
+/// neighbouring characters mostly share a colour.
+const COLOR_LEVELS: u16 = 4;
+
+/// A background converted keeping the colour of its pixels, as runs of
+/// characters of the same colour. Line breaks are part of the runs.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ColoredAscii(pub Vec<(String, [u8; 3])>);
+
+impl ColoredAscii {
+    /// All in one colour, for the plain mode and placeholders.
+    pub fn uniform(text: String, rgb: [u8; 3]) -> Self {
+        Self(vec![(text, rgb)])
+    }
+
+    fn push(&mut self, c: &str, rgb: [u8; 3]) {
+        match self.0.last_mut() {
+            Some((text, last)) if *last == rgb || c == "\n" => text.push_str(c),
+            _ => self.0.push((c.into(), rgb)),
+        }
+    }
+
+    /// With 24 bit ANSI colour codes, for terminals.
+    pub fn to_ansi(&self) -> String {
+        let mut ansi = String::new();
+        for (text, [r, g, b]) in self.0.iter() {
+            ansi += &format!("\x1b[38;2;{};{};{}m{}", r, g, b, text);
+        }
+        ansi += "\x1b[0m";
+        ansi
+    }
+}
+
+fn quantize(channel: u8, alpha: f32) -> u8 {
+    let step = 255 / (COLOR_LEVELS - 1);
+    let scaled = (channel as f32 * alpha) as u16;
+    ((scaled + step / 2) / step * step) as u8
+}
+
+/// Same characters as `convert_image_to_ascii`, the colour channel weights
+/// only matter for picking them.
+pub fn convert_image_to_colored_ascii(
+    bg: &Background,
+    image: &RgbaImage,
+    alpha: f32,
+) -> ColoredAscii {
+    let mut colored = ColoredAscii::default();
+    for (i, p) in image.pixels().enumerate() {
+        let intensity = (pixel_to_intensity(bg, p) as f32 * alpha) as u8;
+        let rgb = [
+            quantize(p.0[0], alpha),
+            quantize(p.0[1], alpha),
+            quantize(p.0[2], alpha),
+        ];
+        colored.push(intensity_to_ascii(intensity), rgb);
+        if (i + 1) % WIDTH as usize == 0 {
+            colored.push("\n", rgb);
+        }
+    }
+    colored
+}
+
+/// Frame of the size of a converted background with a message in the middle,
+/// shown in place of images that could not be loaded.
+pub fn placeholder_ascii(message: &str) -> String {
+    let inner = WIDTH as usize - 2;
+    let message: String = message.chars().take(inner - 2).collect();
+    let mut frame = format!("+{}+\n", "-".repeat(inner));
+    for row in 1..HEIGHT - 1 {
+        if row == HEIGHT / 2 {
+            frame += &format!("|{:^width$}|\n", message, width = inner);
+        } else {
+            frame += &format!("|{}|\n", " ".repeat(inner));
+        }
+    }
+    frame += &format!("+{}+\n", "-".repeat(inner));
+    frame
+}
+
+fn pixel_to_intensity(bg: &Background, p: &image::Rgba<u8>) -> u8 {
+    let p = p.0;
+    let cc = bg.color_channels();
+    let (r, g, b) = (p[0] as usize, p[1] as usize, p[2] as usize);
+    (if cc.0 == cc.1 && cc.1 == cc.2 {
+        (r + g + b) / 3
+    } else {
+        (r * cc.0 + g * cc.1 + b * cc.2) / (cc.0 + cc.1 + cc.2)
+    }) as u8
+}
+
+// Copied from edelsonc/asciify
+fn intensity_to_ascii(value: u8) -> &'static str {
+    let ascii_chars = [
+        ".", "^", ",", ":", "_", "=", "~", "+", "O", "o", "*", "#", "&", "%", "B", "@", "$",
+    ];
+
+    let n_chars = ascii_chars.len() as u8;
+    let step = 255u8 / n_chars;
+    for i in 1..(n_chars - 1) {
+        let comp = step * i;
+        if value < comp {
+            let idx = (i - 1) as usize;
+            return ascii_chars[idx];
+        }
+    }
+
+    ascii_chars[(n_chars - 1) as usize]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn background() -> Background {
+        serde_json::from_value(serde_json::json!({
+            "id": "hall",
+            "name": "hall",
+            "url": "hall.png",
+            "color_channels": [255, 255, 255],
+        }))
+        .unwrap()
+    }
+
+    /// Left half red and right half blue.
+    fn halves() -> RgbaImage {
+        RgbaImage::from_fn(WIDTH, 2, |x, _| {
+            if x < WIDTH / 2 {
+                image::Rgba([250, 10, 10, 255])
+            } else {
+                image::Rgba([10, 10, 250, 255])
+            }
+        })
+    }
+
+    #[test]
+    fn quantize_rounds_to_the_nearest_level() {
+        assert_eq!(quantize(0, 1.0), 0);
+        assert_eq!(quantize(40, 1.0), 0);
+        assert_eq!(quantize(50, 1.0), 85);
+        assert_eq!(quantize(250, 1.0), 255);
+        assert_eq!(quantize(250, 0.5), 85);
+    }
+
+    #[test]
+    fn colored_ascii_has_a_run_per_colour_and_the_same_characters() {
+        let bg = background();
+        let image = halves();
+        let colored = convert_image_to_colored_ascii(&bg, &image, 1.0);
+
+        assert_eq!(
+            colored.0.iter().map(|(_, rgb)| *rgb).collect::<Vec<_>>(),
+            vec![[255, 0, 0], [0, 0, 255], [255, 0, 0], [0, 0, 255]]
+        );
+        let text: String = colored.0.iter().map(|(t, _)| t.as_str()).collect();
+        assert_eq!(text, convert_image_to_ascii(&bg, &image, 1.0));
+    }
+
+    #[test]
+    fn line_breaks_join_the_run_before_them() {
+        let colored = convert_image_to_colored_ascii(&background(), &halves(), 1.0);
+
+        assert!(colored.0[1].0.ends_with('\n'));
+        assert!(!colored.0[2].0.contains('\n'));
+    }
+
+    #[test]
+    fn to_ansi_colours_every_run_and_resets_at_the_end() {
+        let colored = ColoredAscii(vec![("ab\n".into(), [255, 0, 0]), ("c".into(), [0, 85, 255])]);
+
+        assert_eq!(
+            colored.to_ansi(),
+            "\x1b[38;2;255;0;0mab\n\x1b[38;2;0;85;255mc\x1b[0m"
+        );
+    }
+}
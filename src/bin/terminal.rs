@@ -0,0 +1,168 @@
+use std::{
+    collections::HashMap,
+    env,
+    io::{self, BufRead, Write},
+};
+
+use fni2stib8::{
+    ascii::{convert_image_to_ascii, convert_image_to_colored_ascii, placeholder_ascii},
+    game_state::GameState,
+    images::load_background_image,
+    model::Crudable,
+    project::Project,
+    story::{Progress, Story},
+};
+
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+const DIM: &str = "\x1b[2m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// Escape codes are left out when asked to, or when the terminal would not
+/// understand them.
+fn ansi_enabled() -> bool {
+    !env::args().any(|arg| arg == "--plain")
+        && env::var_os("NO_COLOR").is_none()
+        && env::var("TERM").map_or(false, |term| term != "dumb")
+}
+
+/// Backgrounds in the colours of their images with `--color`, for terminals
+/// with 24 bit colour.
+fn color_enabled() -> bool {
+    env::args().any(|arg| arg == "--color")
+}
+
+/// Story text must not be able to move the cursor or recolor the terminal.
+fn sanitize(text: &str) -> String {
+    text.chars()
+        .filter(|c| *c == '\n' || *c == '\t' || !c.is_control())
+        .collect()
+}
+
+struct Style {
+    ansi: bool,
+}
+
+impl Style {
+    fn paint(&self, code: &str, text: &str) -> String {
+        if self.ansi {
+            format!("{}{}{}", code, text, RESET)
+        } else {
+            text.into()
+        }
+    }
+}
+
+/// Plays the story on stdout reading choices from stdin, without the editor
+/// or Bevy. Takes `--project <dir>`, `--plain` and `--color`.
+fn main() {
+    std::process::exit(run(&Project::from_args()));
+}
+
+/// Gives the exit code.
+fn run(project: &Project) -> i32 {
+    let story = match Story::load(project) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return 2;
+        }
+    };
+    let style = Style {
+        ansi: ansi_enabled(),
+    };
+    let colored = style.ansi && color_enabled();
+    let mut ascii_backgrounds: HashMap<String, String> = HashMap::new();
+    let mut game_state = GameState::default();
+    let mut progress = Progress::starting_at(story.start());
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut message = String::new();
+
+    loop {
+        let slide = match story.slide(&progress.current_slide) {
+            None => {
+                eprintln!("error: the slide \"{}\" does not exist", progress.current_slide);
+                return 1;
+            }
+            Some(s) => s,
+        };
+        progress.enter(slide, &mut game_state);
+
+        if style.ansi {
+            print!("{}", CLEAR_SCREEN);
+        }
+        if let Some(bg) = story.background_of(slide) {
+            // Kept with its escape codes already in.
+            let ascii = ascii_backgrounds.entry(bg.id()).or_insert_with(|| {
+                match load_background_image(project, bg) {
+                    Ok(image) if colored => {
+                        convert_image_to_colored_ascii(bg, &image, 1.0).to_ansi()
+                    }
+                    Ok(image) => style.paint(DIM, &convert_image_to_ascii(bg, &image, 1.0)),
+                    Err(e) => {
+                        let message = format!("[ {}: {} ]", bg.name(), e);
+                        style.paint(DIM, &placeholder_ascii(&message))
+                    }
+                }
+            });
+            println!("{}", ascii);
+        }
+        println!("{}\n", sanitize(&slide.description));
+        if !game_state.inventory.is_empty() {
+            println!("Inventory: {}\n", sanitize(&game_state.inventory.join(", ")));
+        }
+
+        let actions = slide.available_actions(&game_state);
+        if actions.is_empty() {
+            if slide.ending {
+                println!("{}", style.paint(BOLD, "The End"));
+                return 0;
+            }
+            // A mistake in the story, unless every action is hidden by its
+            // condition. Going back may still lead somewhere else.
+            println!("{}", style.paint(BOLD, "Dead end: there is nothing to do here."));
+            if !progress.can_step_back() {
+                return 1;
+            }
+        }
+        for (i, a) in actions.iter().enumerate() {
+            println!("{} {}", style.paint(BOLD, &format!("{}.", i + 1)), sanitize(&a.text));
+        }
+        println!();
+        if !message.is_empty() {
+            println!("{}", message);
+            message.clear();
+        }
+        if actions.is_empty() {
+            print!("b to go back, q to quit: ");
+        } else {
+            print!("Choose 1-{}, b to go back, q to quit: ", actions.len());
+        }
+        io::stdout().flush().ok();
+
+        let line = match lines.next() {
+            None => return 0,
+            Some(Err(e)) => {
+                eprintln!("error: {}", e);
+                return 2;
+            }
+            Some(Ok(line)) => line,
+        };
+        match line.trim() {
+            "q" => return 0,
+            "b" => {
+                if !progress.step_back(&mut game_state) {
+                    message = "There is nowhere to go back to.".into();
+                }
+            }
+            choice => match choice.parse::<usize>().ok().and_then(|n| actions.get(n.wrapping_sub(1))) {
+                Some(a) => {
+                    let next = progress.choose(a, &mut game_state);
+                    progress.go_to(next);
+                }
+                None => message = format!("\"{}\" is not one of the choices.", choice),
+            },
+        }
+    }
+}
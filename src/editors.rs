@@ -1,5 +1,15 @@
-use crate::{images::Background, model::*};
-use crate::persistence::PersistenceEvent;
+use crate::{
+    game_state::{parse_condition, Effect, Value},
+    images::Background,
+    items::Item,
+    lint::Problem,
+    manifest::StoryManifest,
+    model::*,
+    story::Story,
+};
+use crate::graph_view::GraphView;
+use crate::persistence::{PersistenceEvent, Persistable};
+use crate::unsaved::UnsavedChanges;
 use bevy::prelude::*;
 use bevy_egui::{
     egui::{self, ScrollArea},
@@ -11,14 +21,19 @@ pub struct EditorsPlugin;
 impl Plugin for EditorsPlugin {
     fn build(&self, builder: &mut AppBuilder) {
         builder
+            .insert_resource(GraphView::default())
+            .insert_resource(Problems::default())
             .add_system(slide_list.system())
+            .add_system(GraphView::render.system())
+            .add_system(Problems::update.system())
+            .add_system(problems.system())
             .add_system(SlideEditor::render.system())
-            .add_system(SlideEditor::handle_renames.system())
             .add_system(AddSlidePrompt::render.system())
             .add_system(RenameDialog::<Slide>::render.system())
             .add_system(DeleteSlideDialog::render.system());
     }
 }
+/// Holds the id of the slide to delete.
 struct DeleteSlideDialog(String);
 impl DeleteSlideDialog {
     fn render(
@@ -39,11 +54,15 @@ impl DeleteSlideDialog {
             .filter(|s| s.actions.iter().any(|a| a.target_slide == dialog.0))
             .map(|s| s.name.clone())
             .collect();
+        let name = slides
+            .iter()
+            .find(|s| s.id == dialog.0)
+            .map_or(dialog.0.clone(), |s| s.name.clone());
 
         if !editors_open.0  { return; }
         egui::Window::new("Delete slide").show(egui_context.ctx(), |ui| {
             ui.horizontal(|ui| {
-                ui.label(format!("Goging to delete \"{}\"", dialog.0));
+                ui.label(format!("Goging to delete \"{}\"", name));
             });
             ui.horizontal(|ui| {
                 if ui.button("Cancel").clicked() {
@@ -66,6 +85,7 @@ impl DeleteSlideDialog {
 }
 
 pub struct RenameDialog<R> {
+    id: String,
     old_name: String,
     new_name: String,
     _phantom: std::marker::PhantomData<R>,
@@ -74,6 +94,7 @@ pub struct RenameDialog<R> {
 impl<R: 'static + Crudable> RenameDialog<R> {
     pub fn new_for(res: &R) -> Self {
         Self {
+            id: res.id(),
             old_name: res.name().clone(),
             new_name: res.name().clone(),
             _phantom: Default::default(),
@@ -115,7 +136,7 @@ impl<R: 'static + Crudable> RenameDialog<R> {
                     } else {
                         if ui.button("Confirm rename").clicked() {
                             crud_events.send(CrudEvent::Renamed(
-                                dialog.old_name.clone(),
+                                dialog.id.clone(),
                                 dialog.new_name.clone(),
                             ));
                             commands.remove_resource::<Self>();
@@ -133,13 +154,20 @@ fn slide_list(
     mut commands: Commands,
     mut slide_events: EventWriter<CrudEvent<Slide>>,
     editors_open: Res<EditorsOpen>,
+    mut graph_view: ResMut<GraphView>,
+    unsaved: Res<UnsavedChanges>,
 ) {
     if !editors_open.0  { return; }
-    egui::Window::new("Slides").show(egui_context.ctx(), |ui| {
+    let title = format!("Slides{}", UnsavedChanges::marker(unsaved.contains(Slide::file_name())));
+    // Keeps its place while the title changes.
+    egui::Window::new(title).id(egui::Id::new("slide_list")).show(egui_context.ctx(), |ui| {
         ui.horizontal(|ui| {
             if ui.button("Add new").clicked() {
                 commands.insert_resource(AddSlidePrompt::default());
             }
+            if ui.button("Graph").clicked() {
+                graph_view.open = !graph_view.open;
+            }
         });
 
         ui.separator();
@@ -148,10 +176,10 @@ fn slide_list(
             ui.horizontal(|ui| {
                 ui.label(format!("{}", s.name,));
                 if ui.small_button("edit").clicked() {
-                    commands.spawn().insert(SlideEditor::new_for(&s.name));
+                    commands.spawn().insert(SlideEditor::new_for(&s.id));
                 }
                 if ui.small_button("remove").clicked() {
-                    commands.insert_resource(DeleteSlideDialog(s.name.clone()));
+                    commands.insert_resource(DeleteSlideDialog(s.id.clone()));
                 }
                 if ui.small_button("rename").clicked() {
                     commands.insert_resource(RenameDialog::new_for(s));
@@ -161,50 +189,112 @@ fn slide_list(
     });
 }
 
-struct SlideEditor {
+/// What `Story::validate` found.
+#[derive(Default)]
+struct Problems(Vec<Problem>);
+
+impl Problems {
+    /// Validates again only when the slides, backgrounds or manifest change.
+    fn update(
+        mut problems: ResMut<Self>,
+        slides: Query<&Slide>,
+        backgrounds: Query<&Background>,
+        changed_slides: Query<(), Changed<Slide>>,
+        changed_backgrounds: Query<(), Changed<Background>>,
+        removed_slides: RemovedComponents<Slide>,
+        removed_backgrounds: RemovedComponents<Background>,
+        manifest: Res<StoryManifest>,
+    ) {
+        if changed_slides.iter().next().is_none()
+            && changed_backgrounds.iter().next().is_none()
+            && removed_slides.iter().next().is_none()
+            && removed_backgrounds.iter().next().is_none()
+            && !manifest.is_changed()
+        {
+            return;
+        }
+        let story = Story {
+            slides: slides.iter().cloned().collect(),
+            backgrounds: backgrounds.iter().cloned().collect(),
+            items: vec![],
+            manifest: manifest.clone(),
+        };
+        problems.0 = story.validate();
+    }
+}
+
+fn problems(
+    egui_context: ResMut<EguiContext>,
+    problems: Res<Problems>,
+    mut commands: Commands,
+    editors_open: Res<EditorsOpen>,
+) {
+    if !editors_open.0  { return; }
+    egui::Window::new(format!("Problems ({})", problems.0.len()))
+        .id(egui::Id::new("problems"))
+        .show(egui_context.ctx(), |ui| {
+            if problems.0.is_empty() {
+                ui.label("No problems found");
+            }
+            ScrollArea::auto_sized().show(ui, |ui| {
+                for p in problems.0.iter() {
+                    match p.slide() {
+                        Some(slide) => {
+                            if ui.small_button(p.to_string()).clicked() {
+                                commands.spawn().insert(SlideEditor::new_for(slide));
+                            }
+                        }
+                        None => {
+                            ui.colored_label(egui::Color32::RED, p.to_string());
+                        }
+                    }
+                }
+            });
+        });
+}
+
+pub(crate) struct SlideEditor {
+    /// Id of the slide being edited.
     target: String,
     ttl: usize,
 }
 
 impl SlideEditor {
-    fn new_for(slide_name: &str) -> Self {
+    pub(crate) fn new_for(slide_id: &str) -> Self {
         Self {
-            target: slide_name.into(),
+            target: slide_id.into(),
             ttl: 3,
         }
     }
-    fn handle_renames(
-        mut editors: Query<&mut Self>,
-        mut slide_events: EventReader<CrudEvent<Slide>>,
-    ) {
-        for ev in slide_events.iter() {
-            match ev {
-                CrudEvent::Renamed(old_name, new_name) => {
-                    for mut e in editors.iter_mut() {
-                        if e.target == *old_name {
-                            e.target = new_name.clone();
-                        }
-                    }
-                }
-                _ => {}
-            }
-        }
-    }
     fn render(
         egui_context: ResMut<EguiContext>,
         mut editors: Query<(Entity, &mut Self)>,
         slides: Query<&Slide>,
         backgrounds: Query<&Background>,
+        items: Query<&Item>,
         mut slide_events: EventWriter<CrudEvent<Slide>>,
         mut commands: Commands,
         editors_open: Res<EditorsOpen>,
     ) {
         let valid_slide_names: Vec<_> = slides.iter().map(|s| s.name.clone()).collect();
+        let slide_name = |id: &str| {
+            slides
+                .iter()
+                .find(|s| s.id == id)
+                .map_or(id.to_string(), |s| s.name.clone())
+        };
+        let background_name = |id: &str| {
+            backgrounds
+                .iter()
+                .find(|b| b.id() == id)
+                .map_or(id.to_string(), |b| b.name())
+        };
+        let item_names: Vec<_> = items.iter().map(|i| i.name.clone()).collect();
 
         if !editors_open.0  { return; }
 
         for (eid, mut e) in editors.iter_mut() {
-            let saved = match slides.iter().filter(|s| s.name == e.target).next() {
+            let saved = match slides.iter().filter(|s| s.id == e.target).next() {
                 None => {
                     if e.ttl > 0 {
                         warn!("{} not found, closing editor in {}", e.target, e.ttl);
@@ -232,10 +322,11 @@ impl SlideEditor {
                     });
                     ui.label("Description:");
                     ui.text_edit_multiline(&mut unsaved.description);
+                    ui.checkbox(&mut unsaved.ending, "Ending (no actions needed)");
                     ui.horizontal(|ui| {
                         ui.label("Background:");
                         egui::ComboBox::from_id_source((eid, "bg"))
-                            .selected_text(&unsaved.background)
+                            .selected_text(background_name(&unsaved.background))
                             .show_ui(ui, |ui| {
                                 ui.selectable_value(
                                     &mut unsaved.background,
@@ -245,12 +336,14 @@ impl SlideEditor {
                                 for bg in backgrounds.iter() {
                                     ui.selectable_value(
                                         &mut unsaved.background,
-                                        bg.name().clone(),
+                                        bg.id(),
                                         &bg.name(),
                                     );
                                 }
                             });
                     });
+                    ui.label("On enter:");
+                    effects_editor(ui, (eid, "enter"), &mut unsaved.effects, &item_names);
                     ui.label("Actions:");
                     ScrollArea::auto_sized().show(ui, |ui| {
                         let mut to_remove = vec![];
@@ -258,18 +351,18 @@ impl SlideEditor {
                             ui.horizontal(|ui| {
                                 ui.text_edit_singleline(&mut a.text);
                                 egui::ComboBox::from_id_source((eid, i))
-                                    .selected_text(&a.target_slide)
+                                    .selected_text(slide_name(&a.target_slide))
                                     .show_ui(ui, |ui| {
                                         ui.selectable_value(
                                             &mut a.target_slide,
                                             "*NEW*".into(),
                                             "*NEW*",
                                         );
-                                        for sn in valid_slide_names.iter() {
+                                        for s in slides.iter() {
                                             ui.selectable_value(
                                                 &mut a.target_slide,
-                                                sn.clone(),
-                                                sn,
+                                                s.id.clone(),
+                                                &s.name,
                                             );
                                         }
                                     });
@@ -280,9 +373,10 @@ impl SlideEditor {
                                         .filter(|name| !valid_slide_names.contains(name))
                                         .next()
                                         .expect("Abusrd amount of badly named slides");
-                                    commands.spawn().insert(Slide::new(name.clone()));
-                                    commands.spawn().insert(SlideEditor::new_for(&name));
-                                    a.target_slide = name;
+                                    let slide = Slide::new(name);
+                                    commands.spawn().insert(SlideEditor::new_for(&slide.id));
+                                    a.target_slide = slide.id.clone();
+                                    slide_events.send(CrudEvent::Created(slide));
                                 }
 
                                 if ui.small_button("->").clicked() {
@@ -294,6 +388,18 @@ impl SlideEditor {
                                     to_remove.push(a.clone());
                                 }
                             });
+                            ui.indent((eid, i, "details"), |ui| {
+                                ui.horizontal(|ui| {
+                                    ui.label("Only if:");
+                                    ui.text_edit_singleline(&mut a.condition);
+                                    if !a.condition.trim().is_empty() {
+                                        if let Err(err) = parse_condition(&a.condition) {
+                                            ui.colored_label(egui::Color32::RED, err);
+                                        }
+                                    }
+                                });
+                                effects_editor(ui, (eid, i), &mut a.effects, &item_names);
+                            });
                         }
                         unsaved.actions.retain(|a| !to_remove.contains(a));
                         if ui.small_button("Add action").clicked() {
@@ -304,15 +410,14 @@ impl SlideEditor {
                     let slides_with_references: Vec<_> = slides
                         .iter()
                         .filter(|s| s.actions.iter().any(|a| a.target_slide == e.target))
-                        .map(|s| s.name.clone())
                         .collect();
 
                     ui.separator();
                     ui.label("Slides referencing this one:");
                     ui.horizontal(|ui| {
                         for rs in slides_with_references {
-                            if ui.small_button(&rs).clicked() {
-                                commands.spawn().insert(SlideEditor::new_for(&rs));
+                            if ui.small_button(&rs.name).clicked() {
+                                commands.spawn().insert(SlideEditor::new_for(&rs.id));
                             }
                         }
                     });
@@ -327,6 +432,90 @@ impl SlideEditor {
     }
 }
 
+fn effects_editor(
+    ui: &mut egui::Ui,
+    id_source: impl std::hash::Hash + Copy,
+    effects: &mut Vec<Effect>,
+    item_names: &[String],
+) {
+    let mut to_remove = None;
+    for (i, effect) in effects.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            let subject = effect.subject_mut().clone();
+            let kinds = [
+                Effect::Set {
+                    var: subject.clone(),
+                    value: Value::Bool(true),
+                },
+                Effect::Increment {
+                    var: subject.clone(),
+                    by: 1,
+                },
+                Effect::Toggle {
+                    var: subject.clone(),
+                },
+                Effect::Give {
+                    item: subject.clone(),
+                },
+                Effect::Take {
+                    item: subject.clone(),
+                },
+            ];
+            egui::ComboBox::from_id_source((id_source, "effect", i))
+                .selected_text(effect.kind())
+                .show_ui(ui, |ui| {
+                    for kind in kinds.iter() {
+                        if ui
+                            .selectable_label(effect.kind() == kind.kind(), kind.kind())
+                            .clicked()
+                        {
+                            *effect = kind.clone();
+                        }
+                    }
+                });
+            match effect {
+                Effect::Set { var, value } => {
+                    ui.text_edit_singleline(var);
+                    ui.label("to");
+                    let mut literal = value.to_literal();
+                    ui.text_edit_singleline(&mut literal);
+                    if literal != value.to_literal() {
+                        *value = Value::parse_literal(&literal);
+                    }
+                }
+                Effect::Increment { var, by } => {
+                    ui.text_edit_singleline(var);
+                    ui.label("by");
+                    let mut amount = *by as f32;
+                    ui.add(egui::DragValue::new(&mut amount).speed(1.));
+                    *by = amount as i64;
+                }
+                Effect::Toggle { var } => {
+                    ui.text_edit_singleline(var);
+                }
+                Effect::Give { item } | Effect::Take { item } => {
+                    egui::ComboBox::from_id_source((id_source, "item", i))
+                        .selected_text(item.clone())
+                        .show_ui(ui, |ui| {
+                            for name in item_names.iter() {
+                                ui.selectable_value(item, name.clone(), name);
+                            }
+                        });
+                }
+            }
+            if ui.small_button("x").clicked() {
+                to_remove = Some(i);
+            }
+        });
+    }
+    if let Some(i) = to_remove {
+        effects.remove(i);
+    }
+    if ui.small_button("Add effect").clicked() {
+        effects.push(Effect::default());
+    }
+}
+
 #[derive(Debug, Default)]
 struct AddSlidePrompt {
     name: String,
@@ -365,7 +554,7 @@ impl AddSlidePrompt {
                         let slide = Slide::new(prompt.name.clone());
 
                         slide_events.send(CrudEvent::Created(slide.clone()));
-                        commands.spawn().insert(SlideEditor::new_for(&slide.name));
+                        commands.spawn().insert(SlideEditor::new_for(&slide.id));
                         commands.remove_resource::<AddSlidePrompt>();
                     }
                 });
@@ -0,0 +1,426 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use serde_json::{json, Value as Json};
+
+use crate::{
+    game_state::{parse_condition, BinOp, Expr},
+    ascii::{convert_image_to_ascii, placeholder_ascii},
+    images::load_background_image,
+    model::Crudable,
+    persistence::{write_text, PersistenceError},
+    project::Project,
+    story::Story,
+};
+
+/// Within the project directory.
+pub const EXPORT_DIR: &str = "export";
+
+/// Conditions travel as a tree for the page to evaluate, so it does not need
+/// a parser of its own. `null` always holds, like an empty condition.
+fn condition_to_json(condition: &str) -> Json {
+    if condition.trim().is_empty() {
+        return Json::Null;
+    }
+    match parse_condition(condition) {
+        Ok(expr) => expr_to_json(&expr),
+        // Conditions that do not parse never hold.
+        Err(_) => json!({ "lit": false }),
+    }
+}
+
+fn expr_to_json(expr: &Expr) -> Json {
+    match expr {
+        Expr::Literal(v) => json!({ "lit": v }),
+        Expr::Var(name) => json!({ "var": name }),
+        Expr::Not(e) => json!({ "not": expr_to_json(e) }),
+        Expr::Has(item) => json!({ "has": item }),
+        Expr::Binary(op, l, r) => {
+            let op = match op {
+                BinOp::And => "and",
+                BinOp::Or => "or",
+                BinOp::Eq => "eq",
+                BinOp::Ne => "ne",
+                BinOp::Lt => "lt",
+                BinOp::Le => "le",
+                BinOp::Gt => "gt",
+                BinOp::Ge => "ge",
+            };
+            json!({ "op": op, "l": expr_to_json(l), "r": expr_to_json(r) })
+        }
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A single page playing the story, with the backgrounds already turned into
+/// ASCII since the page can not do the conversion itself. Both slides and
+/// backgrounds are keyed by id.
+pub fn export_html(story: &Story, ascii_backgrounds: &HashMap<String, String>) -> String {
+    let slides: serde_json::Map<String, Json> = story
+        .slides
+        .iter()
+        .map(|s| {
+            let actions: Vec<Json> = s
+                .actions
+                .iter()
+                .map(|a| {
+                    json!({
+                        "text": a.text,
+                        "target": a.target_slide,
+                        "condition": condition_to_json(&a.condition),
+                        "effects": a.effects,
+                    })
+                })
+                .collect();
+            (
+                s.id.clone(),
+                json!({
+                    "description": s.description,
+                    "background": s.background,
+                    "effects": s.effects,
+                    "actions": actions,
+                    "ending": s.ending,
+                }),
+            )
+        })
+        .collect();
+    let data = json!({
+        "title": story.manifest.title,
+        "start": story.start(),
+        "backgrounds": ascii_backgrounds,
+        "slides": slides,
+    });
+    // Nothing in the data may close the script tag it is embedded in.
+    let data = data.to_string().replace("</", "<\\/");
+    fill_template(
+        PAGE_TEMPLATE,
+        &[
+            ("__TITLE__", &escape_html(&story.manifest.title)),
+            ("__STORY__", &data),
+        ],
+    )
+}
+
+/// Replaces the placeholders in one pass, so nothing filled in is taken for
+/// another placeholder.
+fn fill_template(template: &str, values: &[(&str, &str)]) -> String {
+    let mut filled = String::new();
+    let mut rest = template;
+    loop {
+        let next = values
+            .iter()
+            .filter_map(|(placeholder, value)| {
+                rest.find(placeholder).map(|i| (i, placeholder, value))
+            })
+            .min_by_key(|(i, _, _)| *i);
+        match next {
+            None => {
+                filled += rest;
+                return filled;
+            }
+            Some((i, placeholder, value)) => {
+                filled += &rest[..i];
+                filled += value;
+                rest = &rest[i + placeholder.len()..];
+            }
+        }
+    }
+}
+
+/// Writes the page to `export/index.html` in the project, giving where it
+/// ended up.
+pub fn write_export(project: &Project, html: &str) -> Result<PathBuf, PersistenceError> {
+    let dir = project.path(EXPORT_DIR);
+    fs::create_dir_all(&dir).map_err(|e| PersistenceError::io(&dir, e))?;
+    let path = dir.join("index.html");
+    write_text(&path, html)?;
+    Ok(path)
+}
+
+/// Headless `--export-html` mode, loads the story and its images and writes
+/// the page. Returns the exit code.
+pub fn run_export(project: &Project) -> i32 {
+    let story = match Story::load(project) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return 2;
+        }
+    };
+    let ascii_backgrounds: HashMap<String, String> = story
+        .backgrounds
+        .iter()
+        .map(|bg| {
+            let ascii = match load_background_image(project, bg) {
+                Ok(image) => convert_image_to_ascii(bg, &image, 1.0),
+                Err(e) => {
+                    eprintln!("warning: background {}: {}", bg.name(), e);
+                    placeholder_ascii(&format!("[ {} ]", bg.name()))
+                }
+            };
+            (bg.id(), ascii)
+        })
+        .collect();
+    match write_export(project, &export_html(&story, &ascii_backgrounds)) {
+        Ok(path) => {
+            println!("Exported {} slides to {}", story.slides.len(), path.display());
+            0
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            1
+        }
+    }
+}
+
+const PAGE_TEMPLATE: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>__TITLE__</title>
+<style>
+  body { background: #000; color: #fff; font-family: monospace; text-align: center; }
+  #background { font-size: 8px; line-height: 8px; color: #aaa; margin: 2em auto 1em; }
+  #description { white-space: pre-wrap; max-width: 60em; margin: 1em auto; min-height: 3em; }
+  #actions { list-style: none; padding: 0; }
+  #actions li { margin: 1em; cursor: pointer; }
+  #actions li:hover::before { content: "--> "; }
+  #actions li:hover::after { content: " <--"; }
+  #inventory { position: fixed; right: 2em; bottom: 2em; text-align: left; white-space: pre; }
+</style>
+</head>
+<body>
+<pre id="background"></pre>
+<div id="description"></div>
+<ul id="actions"></ul>
+<div id="inventory"></div>
+<script type="application/json" id="story">__STORY__</script>
+<script>
+"use strict";
+const story = JSON.parse(document.getElementById("story").textContent);
+// Same pace as the native player: 4.7 letters a word, 4 words a second.
+const LETTERS_PER_SECOND = 4.7 * 4;
+
+let state, current, typing;
+
+function truthy(v) {
+  if (typeof v === "string") return v.length > 0;
+  if (typeof v === "number") return v !== 0;
+  return !!v;
+}
+
+function get(name) {
+  return name in state.variables ? state.variables[name] : false;
+}
+
+function evaluate(e) {
+  if (e === null) return true;
+  if ("lit" in e) return e.lit;
+  if ("var" in e) return get(e.var);
+  if ("not" in e) return !truthy(evaluate(e.not));
+  if ("has" in e) return state.inventory.includes(e.has);
+  if (e.op === "and") return truthy(evaluate(e.l)) && truthy(evaluate(e.r));
+  if (e.op === "or") return truthy(evaluate(e.l)) || truthy(evaluate(e.r));
+  const l = evaluate(e.l), r = evaluate(e.r);
+  if (e.op === "eq") return l === r;
+  if (e.op === "ne") return l !== r;
+  if (typeof l !== "number" || typeof r !== "number") return false;
+  if (e.op === "lt") return l < r;
+  if (e.op === "le") return l <= r;
+  if (e.op === "gt") return l > r;
+  if (e.op === "ge") return l >= r;
+  return false;
+}
+
+function apply(effect) {
+  switch (effect.op) {
+    case "set": state.variables[effect.var] = effect.value; break;
+    case "increment": {
+      const v = get(effect.var);
+      state.variables[effect.var] = (typeof v === "number" ? v : 0) + effect.by;
+      break;
+    }
+    case "toggle": state.variables[effect.var] = !truthy(get(effect.var)); break;
+    case "give": if (!state.inventory.includes(effect.item)) state.inventory.push(effect.item); break;
+    case "take": state.inventory = state.inventory.filter(i => i !== effect.item); break;
+  }
+}
+
+function availableActions() {
+  return story.slides[current].actions.filter(a => truthy(evaluate(a.condition)));
+}
+
+function showActions() {
+  const list = document.getElementById("actions");
+  list.innerHTML = "";
+  const actions = availableActions();
+  actions.forEach((a, i) => {
+    const li = document.createElement("li");
+    li.textContent = a.text;
+    li.onclick = ev => { ev.stopPropagation(); choose(a); };
+    list.appendChild(li);
+  });
+  if (actions.length === 0) {
+    const li = document.createElement("li");
+    // Like the native player, a slide left without actions that is not an
+    // ending is a mistake in the story.
+    li.textContent = story.slides[current].ending
+      ? "The End - start over"
+      : "Dead end: there is nothing to do here - start over";
+    li.onclick = ev => { ev.stopPropagation(); start(); };
+    list.appendChild(li);
+  }
+}
+
+function finishTyping() {
+  if (!typing) return;
+  clearInterval(typing.timer);
+  document.getElementById("description").textContent = typing.text;
+  typing = null;
+  showActions();
+}
+
+function enter(name) {
+  finishTyping();
+  const slide = story.slides[name];
+  if (!slide) {
+    document.getElementById("description").textContent =
+      "Can not go on with the story: the slide \"" + name + "\" does not exist.";
+    document.getElementById("actions").innerHTML = "";
+    return;
+  }
+  current = name;
+  slide.effects.forEach(apply);
+  document.getElementById("background").textContent = story.backgrounds[slide.background] || "";
+  document.getElementById("inventory").textContent =
+    state.inventory.length ? "Inventory:\n\n" + state.inventory.join("\n") : "";
+  document.getElementById("actions").innerHTML = "";
+  const description = document.getElementById("description");
+  description.textContent = "";
+  let shown = 0;
+  typing = {
+    text: slide.description,
+    timer: setInterval(() => {
+      shown += 1;
+      description.textContent = slide.description.slice(0, shown);
+      if (shown >= slide.description.length) finishTyping();
+    }, 1000 / LETTERS_PER_SECOND),
+  };
+}
+
+function choose(action) {
+  action.effects.forEach(apply);
+  enter(action.target);
+}
+
+function start() {
+  state = { variables: {}, inventory: [] };
+  enter(story.start);
+}
+
+document.body.onclick = finishTyping;
+document.onkeydown = ev => {
+  if (typing) {
+    if (ev.key === "Enter" || ev.key === " ") finishTyping();
+    return;
+  }
+  const n = parseInt(ev.key, 10);
+  const actions = availableActions();
+  if (n >= 1 && n <= actions.length) choose(actions[n - 1]);
+};
+document.title = story.title;
+start();
+</script>
+</body>
+</html>
+"##;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        game_state::Effect,
+        model::{Action, Slide},
+        story::fixtures::{action, slide},
+    };
+
+    /// The story data embedded in the page.
+    fn embedded_story(html: &str) -> Json {
+        let start = html.find(r#"<script type="application/json" id="story">"#).unwrap();
+        let data = &html[start..];
+        let data = &data[data.find('>').unwrap() + 1..data.find("</script>").unwrap()];
+        // `<\/` is an escaped `/` to JSON as well.
+        serde_json::from_str(data).unwrap()
+    }
+
+    #[test]
+    fn embeds_the_story_in_the_page() {
+        let mut story = Story::default();
+        story.manifest.title = "Cats & <Dogs>".into();
+        story.manifest.start_slide = "hall".into();
+        let open = action("Open", "hall", vec![Effect::Toggle { var: "locked".into() }]);
+        story.slides = vec![Slide {
+            description: "A door </script> here".into(),
+            background: "bg-1".into(),
+            ..slide(
+                "hall",
+                vec![Action {
+                    condition: "has(key) && !locked".into(),
+                    ..open
+                }],
+            )
+        }];
+        let backgrounds = vec![("bg-1".to_string(), "#+#".to_string())].into_iter().collect();
+        let html = export_html(&story, &backgrounds);
+
+        assert!(html.contains("<title>Cats &amp; &lt;Dogs&gt;</title>"));
+        // The description can not end the script holding it.
+        assert_eq!(html.matches("</script>").count(), 2);
+        assert!(html.contains("function start()"));
+        assert_eq!(
+            embedded_story(&html),
+            json!({
+                "title": "Cats & <Dogs>",
+                "start": "hall",
+                "backgrounds": { "bg-1": "#+#" },
+                "slides": {
+                    "hall": {
+                        "description": "A door </script> here",
+                        "background": "bg-1",
+                        "effects": [],
+                        "actions": [{
+                            "text": "Open",
+                            "target": "hall",
+                            "condition": {
+                                "op": "and",
+                                "l": { "has": "key" },
+                                "r": { "not": { "var": "locked" } },
+                            },
+                            "effects": [{ "op": "toggle", "var": "locked" }],
+                        }],
+                        "ending": false,
+                    },
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn fills_in_the_title_without_the_story() {
+        let mut story = Story::default();
+        story.manifest.title = "The __STORY__ so far".into();
+        story.slides = vec![Slide {
+            ending: true,
+            ..slide("end", vec![])
+        }];
+        let html = export_html(&story, &HashMap::new());
+
+        assert!(html.contains("<title>The __STORY__ so far</title>"));
+        assert_eq!(embedded_story(&html)["slides"]["end"]["ending"], json!(true));
+    }
+}
@@ -0,0 +1,569 @@
+use std::{collections::BTreeMap, fmt, iter::Peekable, ops::Range, str::CharIndices};
+
+use serde::*;
+use tracing::warn;
+
+/// A story variable. Missing variables read as `false`.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Value {
+    Bool(bool),
+    Int(i64),
+    Str(String),
+}
+
+impl Default for Value {
+    fn default() -> Self {
+        Value::Bool(false)
+    }
+}
+
+impl Value {
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Bool(b) => *b,
+            Value::Int(i) => *i != 0,
+            Value::Str(s) => !s.is_empty(),
+        }
+    }
+
+    /// Parses what a writer types in the editor: `true`, `false`, an integer,
+    /// a `"quoted string"` or anything else as a bare string.
+    pub fn parse_literal(text: &str) -> Self {
+        let text = text.trim();
+        if text.len() >= 2 && text.starts_with('"') && text.ends_with('"') {
+            return Value::Str(text[1..text.len() - 1].into());
+        }
+        match text {
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            _ => match text.parse::<i64>() {
+                Ok(i) => Value::Int(i),
+                Err(_) => Value::Str(text.into()),
+            },
+        }
+    }
+
+    /// Inverse of `parse_literal`, strings are only quoted when they would
+    /// otherwise be read back as something else.
+    pub fn to_literal(&self) -> String {
+        match self {
+            Value::Str(s) if Value::parse_literal(s) != *self => format!("\"{}\"", s),
+            v => v.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Str(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+/// Changes to the game state, attached to actions (applied when chosen) and
+/// to slides (applied when entered). `Give` and `Take` work on item names.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Effect {
+    Set { var: String, value: Value },
+    Increment { var: String, by: i64 },
+    Toggle { var: String },
+    Give { item: String },
+    Take { item: String },
+}
+
+impl Effect {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Effect::Set { .. } => "set",
+            Effect::Increment { .. } => "increment",
+            Effect::Toggle { .. } => "toggle",
+            Effect::Give { .. } => "give",
+            Effect::Take { .. } => "take",
+        }
+    }
+
+    /// The variable or item the effect works on.
+    pub fn subject_mut(&mut self) -> &mut String {
+        match self {
+            Effect::Set { var, .. } | Effect::Increment { var, .. } | Effect::Toggle { var } => var,
+            Effect::Give { item } | Effect::Take { item } => item,
+        }
+    }
+
+    pub fn item(&self) -> Option<&str> {
+        match self {
+            Effect::Give { item } | Effect::Take { item } => Some(item),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Effect {
+    fn default() -> Self {
+        Effect::Set {
+            var: String::new(),
+            value: Value::Bool(true),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, Default)]
+pub struct GameState {
+    pub variables: BTreeMap<String, Value>,
+    /// Names of the items carried, in the order they were picked up.
+    #[serde(default)]
+    pub inventory: Vec<String>,
+}
+
+impl GameState {
+    pub fn get(&self, var: &str) -> Value {
+        self.variables.get(var).cloned().unwrap_or_default()
+    }
+
+    pub fn has(&self, item: &str) -> bool {
+        self.inventory.iter().any(|i| i == item)
+    }
+
+    pub fn apply(&mut self, effect: &Effect) {
+        match effect {
+            Effect::Set { var, value } => {
+                self.variables.insert(var.clone(), value.clone());
+            }
+            Effect::Increment { var, by } => {
+                let current = match self.get(var) {
+                    Value::Int(i) => i,
+                    _ => 0,
+                };
+                self.variables.insert(var.clone(), Value::Int(current.saturating_add(*by)));
+            }
+            Effect::Toggle { var } => {
+                let current = self.get(var).is_truthy();
+                self.variables.insert(var.clone(), Value::Bool(!current));
+            }
+            Effect::Give { item } => {
+                if !self.has(item) {
+                    self.inventory.push(item.clone());
+                }
+            }
+            Effect::Take { item } => {
+                self.inventory.retain(|i| i != item);
+            }
+        }
+    }
+
+    pub fn apply_all(&mut self, effects: &[Effect]) {
+        for e in effects.iter() {
+            self.apply(e);
+        }
+    }
+
+    /// Empty conditions always hold, the ones that do not parse never do.
+    pub fn check(&self, condition: &str) -> bool {
+        if condition.trim().is_empty() {
+            return true;
+        }
+        match parse_condition(condition) {
+            Ok(expr) => expr.eval(self).is_truthy(),
+            Err(e) => {
+                warn!("Invalid condition {:?}: {}", condition, e);
+                false
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BinOp {
+    And,
+    Or,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Expr {
+    Literal(Value),
+    Var(String),
+    Not(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    Has(String),
+}
+
+impl Expr {
+    pub fn eval(&self, state: &GameState) -> Value {
+        match self {
+            Expr::Literal(v) => v.clone(),
+            Expr::Var(name) => state.get(name),
+            Expr::Not(e) => Value::Bool(!e.eval(state).is_truthy()),
+            Expr::Has(item) => Value::Bool(state.has(item)),
+            Expr::Binary(BinOp::And, l, r) => {
+                Value::Bool(l.eval(state).is_truthy() && r.eval(state).is_truthy())
+            }
+            Expr::Binary(BinOp::Or, l, r) => {
+                Value::Bool(l.eval(state).is_truthy() || r.eval(state).is_truthy())
+            }
+            Expr::Binary(op, l, r) => {
+                let (l, r) = (l.eval(state), r.eval(state));
+                Value::Bool(match (op, &l, &r) {
+                    (BinOp::Eq, _, _) => l == r,
+                    (BinOp::Ne, _, _) => l != r,
+                    (BinOp::Lt, Value::Int(a), Value::Int(b)) => a < b,
+                    (BinOp::Le, Value::Int(a), Value::Int(b)) => a <= b,
+                    (BinOp::Gt, Value::Int(a), Value::Int(b)) => a > b,
+                    (BinOp::Ge, Value::Int(a), Value::Int(b)) => a >= b,
+                    _ => false,
+                })
+            }
+        }
+    }
+
+    pub fn mentions_item(&self, item: &str) -> bool {
+        match self {
+            Expr::Has(i) => i == item,
+            Expr::Not(e) => e.mentions_item(item),
+            Expr::Binary(_, l, r) => l.mentions_item(item) || r.mentions_item(item),
+            Expr::Literal(_) | Expr::Var(_) => false,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Token {
+    Ident(String),
+    Int(i64),
+    Str(String),
+    Op(&'static str),
+    Open,
+    Close,
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    Ok(tokenize_with_spans(source)?
+        .into_iter()
+        .map(|(t, _)| t)
+        .collect())
+}
+
+fn tokenize_with_spans(source: &str) -> Result<Vec<(Token, Range<usize>)>, String> {
+    let mut tokens = vec![];
+    let mut chars: Peekable<CharIndices> = source.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        let token = if c.is_whitespace() {
+            chars.next();
+            continue;
+        } else if c == '(' {
+            chars.next();
+            Token::Open
+        } else if c == ')' {
+            chars.next();
+            Token::Close
+        } else if c == '"' {
+            chars.next();
+            let mut s = String::new();
+            loop {
+                match chars.next() {
+                    Some((_, '"')) => break,
+                    Some((_, c)) => s.push(c),
+                    None => return Err("Unterminated string".into()),
+                }
+            }
+            Token::Str(s)
+        } else if c.is_ascii_digit() {
+            let mut s = String::new();
+            while let Some(&(_, c)) = chars.peek() {
+                if !c.is_ascii_digit() {
+                    break;
+                }
+                s.push(c);
+                chars.next();
+            }
+            Token::Int(s.parse().map_err(|_| "Number too big")?)
+        } else if c.is_alphanumeric() || c == '_' {
+            let mut s = String::new();
+            while let Some(&(_, c)) = chars.peek() {
+                if !(c.is_alphanumeric() || c == '_') {
+                    break;
+                }
+                s.push(c);
+                chars.next();
+            }
+            Token::Ident(s)
+        } else {
+            chars.next();
+            let next = chars.peek().map(|(_, c)| *c);
+            let op = match (c, next) {
+                ('&', Some('&')) => "&&",
+                ('|', Some('|')) => "||",
+                ('=', Some('=')) => "==",
+                ('!', Some('=')) => "!=",
+                ('<', Some('=')) => "<=",
+                ('>', Some('=')) => ">=",
+                ('<', _) => "<",
+                ('>', _) => ">",
+                ('!', _) => "!",
+                ('-', _) => "-",
+                _ => return Err(format!("Unexpected character '{}'", c)),
+            };
+            if op.len() == 2 {
+                chars.next();
+            }
+            Token::Op(op)
+        };
+        let end = chars.peek().map(|(i, _)| *i).unwrap_or_else(|| source.len());
+        tokens.push((token, start..end));
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let t = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        t
+    }
+
+    fn eat_op(&mut self, op: &'static str) -> bool {
+        if self.peek() == Some(&Token::Op(op)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.and()?;
+        while self.eat_op("||") {
+            lhs = Expr::Binary(BinOp::Or, Box::new(lhs), Box::new(self.and()?));
+        }
+        Ok(lhs)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.not()?;
+        while self.eat_op("&&") {
+            lhs = Expr::Binary(BinOp::And, Box::new(lhs), Box::new(self.not()?));
+        }
+        Ok(lhs)
+    }
+
+    fn not(&mut self) -> Result<Expr, String> {
+        if self.eat_op("!") {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let lhs = self.atom()?;
+        let op = match self.peek() {
+            Some(Token::Op("==")) => BinOp::Eq,
+            Some(Token::Op("!=")) => BinOp::Ne,
+            Some(Token::Op("<")) => BinOp::Lt,
+            Some(Token::Op("<=")) => BinOp::Le,
+            Some(Token::Op(">")) => BinOp::Gt,
+            Some(Token::Op(">=")) => BinOp::Ge,
+            _ => return Ok(lhs),
+        };
+        self.pos += 1;
+        Ok(Expr::Binary(op, Box::new(lhs), Box::new(self.atom()?)))
+    }
+
+    fn atom(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Open) => {
+                let e = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(e),
+                    _ => Err("Missing ')'".into()),
+                }
+            }
+            Some(Token::Int(i)) => Ok(Expr::Literal(Value::Int(i))),
+            Some(Token::Op("-")) => match self.next() {
+                Some(Token::Int(i)) => Ok(Expr::Literal(Value::Int(-i))),
+                _ => Err("Expected a number after '-'".into()),
+            },
+            Some(Token::Str(s)) => Ok(Expr::Literal(Value::Str(s))),
+            Some(Token::Ident(name)) if name == "has" && self.peek() == Some(&Token::Open) => {
+                self.pos += 1;
+                let item = match self.next() {
+                    Some(Token::Ident(item)) | Some(Token::Str(item)) => item,
+                    _ => return Err("Expected an item name in has(...)".into()),
+                };
+                match self.next() {
+                    Some(Token::Close) => Ok(Expr::Has(item)),
+                    _ => Err("Missing ')'".into()),
+                }
+            }
+            Some(Token::Ident(name)) => Ok(match name.as_str() {
+                "true" => Expr::Literal(Value::Bool(true)),
+                "false" => Expr::Literal(Value::Bool(false)),
+                _ => Expr::Var(name),
+            }),
+            Some(t) => Err(format!("Unexpected {:?}", t)),
+            None => Err("Unexpected end of condition".into()),
+        }
+    }
+}
+
+/// Parses conditions like `has(key) && (gold >= 3 || name == "Bob")`.
+pub fn parse_condition(source: &str) -> Result<Expr, String> {
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        pos: 0,
+    };
+    let expr = parser.or()?;
+    match parser.peek() {
+        None => Ok(expr),
+        Some(t) => Err(format!("Unexpected {:?}", t)),
+    }
+}
+
+/// Rewrites every `has(old)` in the condition to refer to `new` instead,
+/// leaving the rest of the text as the writer typed it.
+pub fn rename_item_in_condition(source: &str, old: &str, new: &str) -> String {
+    let tokens = match tokenize_with_spans(source) {
+        Ok(tokens) => tokens,
+        Err(_) => return source.into(),
+    };
+    let mut result = source.to_string();
+    for w in tokens.windows(4).rev() {
+        match (&w[0].0, &w[1].0, &w[2].0, &w[3].0) {
+            (Token::Ident(has), Token::Open, Token::Ident(item), Token::Close)
+            | (Token::Ident(has), Token::Open, Token::Str(item), Token::Close)
+                if has == "has" && item == old =>
+            {
+                result.replace_range(w[2].1.clone(), &format!("\"{}\"", new));
+            }
+            _ => {}
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn var(name: &str) -> Box<Expr> {
+        Box::new(Expr::Var(name.into()))
+    }
+
+    fn int(i: i64) -> Box<Expr> {
+        Box::new(Expr::Literal(Value::Int(i)))
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(
+            parse_condition("a || b && !c"),
+            Ok(Expr::Binary(
+                BinOp::Or,
+                var("a"),
+                Box::new(Expr::Binary(
+                    BinOp::And,
+                    var("b"),
+                    Box::new(Expr::Not(var("c")))
+                ))
+            ))
+        );
+        assert_eq!(
+            parse_condition("(a || b) && c"),
+            Ok(Expr::Binary(
+                BinOp::And,
+                Box::new(Expr::Binary(BinOp::Or, var("a"), var("b"))),
+                var("c")
+            ))
+        );
+    }
+
+    #[test]
+    fn comparisons_bind_tighter_than_not() {
+        assert_eq!(
+            parse_condition("!gold >= 3"),
+            Ok(Expr::Not(Box::new(Expr::Binary(BinOp::Ge, var("gold"), int(3)))))
+        );
+    }
+
+    #[test]
+    fn reads_has_and_negative_literals() {
+        assert_eq!(parse_condition("has(key)"), Ok(Expr::Has("key".into())));
+        assert_eq!(parse_condition("has(\"old key\")"), Ok(Expr::Has("old key".into())));
+        assert_eq!(
+            parse_condition("debt > -10"),
+            Ok(Expr::Binary(BinOp::Gt, var("debt"), int(-10)))
+        );
+        // Only a call when followed by parentheses.
+        assert_eq!(parse_condition("has"), Ok(Expr::Var("has".into())));
+        assert!(parse_condition("has()").is_err());
+        assert!(parse_condition("- x").is_err());
+        assert!(parse_condition("a b").is_err());
+        assert!(parse_condition("(a").is_err());
+    }
+
+    #[test]
+    fn checks_conditions() {
+        let mut state = GameState::default();
+        state.apply_all(&[
+            Effect::Give { item: "key".into() },
+            Effect::Set {
+                var: "debt".into(),
+                value: Value::Int(-5),
+            },
+        ]);
+        assert!(state.check(""));
+        assert!(state.check("has(key) && debt > -10"));
+        assert!(!state.check("has(lamp) || debt < -5"));
+        assert!(!state.check("debt >"));
+    }
+
+    #[test]
+    fn increment_saturates() {
+        let mut state = GameState::default();
+        let increment = |by| Effect::Increment {
+            var: "n".into(),
+            by,
+        };
+        state.apply(&increment(i64::MAX));
+        state.apply(&increment(1));
+        assert_eq!(state.get("n"), Value::Int(i64::MAX));
+        state.apply(&increment(i64::MIN));
+        state.apply(&increment(i64::MIN));
+        assert_eq!(state.get("n"), Value::Int(i64::MIN));
+    }
+
+    #[test]
+    fn renames_only_the_item_inside_has() {
+        assert_eq!(
+            rename_item_in_condition("has(key) && key  ==  1 || has( \"key\" )", "key", "gold key"),
+            "has(\"gold key\") && key  ==  1 || has( \"gold key\" )"
+        );
+        assert_eq!(
+            rename_item_in_condition("has(keys) || has(key)", "key", "lamp"),
+            "has(keys) || has(\"lamp\")"
+        );
+        // Left alone when it does not tokenize.
+        assert_eq!(
+            rename_item_in_condition("has(key) && \"open", "key", "lamp"),
+            "has(key) && \"open"
+        );
+    }
+}
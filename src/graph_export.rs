@@ -0,0 +1,391 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    path::PathBuf,
+};
+
+use crate::{
+    export::EXPORT_DIR,
+    lint::reachable_from,
+    model::{Crudable, Slide},
+    persistence::{write_text, PersistenceError},
+    project::Project,
+    story::Story,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+}
+
+impl GraphFormat {
+    fn file_name(&self) -> &'static str {
+        match self {
+            GraphFormat::Dot => "story.dot",
+            GraphFormat::Mermaid => "story.mmd",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GraphOptions {
+    pub color_endings: bool,
+    pub color_unreachable: bool,
+    pub color_broken_links: bool,
+}
+
+impl Default for GraphOptions {
+    fn default() -> Self {
+        Self {
+            color_endings: true,
+            color_unreachable: true,
+            color_broken_links: true,
+        }
+    }
+}
+
+impl GraphOptions {
+    /// Everything highlighted but what is turned off with `--no-endings`,
+    /// `--no-unreachable` or `--no-broken`.
+    pub fn from_args() -> Self {
+        let off = |flag: &str| env::args().any(|arg| arg == flag);
+        Self {
+            color_endings: !off("--no-endings"),
+            color_unreachable: !off("--no-unreachable"),
+            color_broken_links: !off("--no-broken"),
+        }
+    }
+}
+
+/// How a slide should stand out, the first that applies wins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Highlight {
+    None,
+    Ending,
+    Unreachable,
+}
+
+struct Graph<'a> {
+    slides: Vec<&'a Slide>,
+    /// By slide id.
+    highlights: HashMap<&'a str, Highlight>,
+    /// Targets of actions that go nowhere, sorted.
+    missing: Vec<&'a str>,
+}
+
+impl<'a> Graph<'a> {
+    fn new(story: &'a Story, options: &GraphOptions) -> Self {
+        let mut slides: Vec<&Slide> = story.slides.iter().collect();
+        slides.sort_by(|a, b| a.name.cmp(&b.name));
+        let ids: HashSet<&str> = slides.iter().map(|s| s.id.as_str()).collect();
+        // Like the linter, nothing is unreachable without a start slide.
+        let reachable = if ids.contains(story.start()) {
+            Some(reachable_from(&story.slides, story.start()))
+        } else {
+            None
+        };
+        let highlights = story
+            .slides
+            .iter()
+            .map(|s| {
+                let unreachable = reachable.as_ref().map_or(false, |r| {
+                    !r.contains(s.id.as_str()) && s.id != story.manifest.credits_slide
+                });
+                let highlight = if options.color_endings && s.ending {
+                    Highlight::Ending
+                } else if options.color_unreachable && unreachable {
+                    Highlight::Unreachable
+                } else {
+                    Highlight::None
+                };
+                (s.id.as_str(), highlight)
+            })
+            .collect();
+        let mut missing: Vec<&str> = story
+            .slides
+            .iter()
+            .flat_map(|s| s.actions.iter())
+            .map(|a| a.target_slide.as_str())
+            .filter(|t| !ids.contains(t))
+            .collect();
+        missing.sort();
+        missing.dedup();
+        Self {
+            slides,
+            highlights,
+            missing,
+        }
+    }
+}
+
+fn node_label(story: &Story, slide: &Slide) -> Vec<String> {
+    let mut lines = vec![slide.name.clone()];
+    match story.background_of(slide) {
+        Some(bg) => lines.push(format!("bg: {}", bg.name())),
+        None if !slide.background.is_empty() => lines.push(format!("bg: {}", slide.background)),
+        None => {}
+    }
+    lines
+}
+
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn dot_string(text: &str) -> String {
+    format!("\"{}\"", dot_escape(text))
+}
+
+pub fn to_dot(story: &Story, options: &GraphOptions) -> String {
+    let graph = Graph::new(story, options);
+    let mut dot = String::from("digraph story {\n    rankdir=LR;\n    node [shape=box];\n");
+    for s in graph.slides.iter() {
+        let label = node_label(story, s)
+            .iter()
+            .map(|l| dot_escape(l))
+            .collect::<Vec<_>>()
+            .join("\\n");
+        let style = match graph.highlights[s.id.as_str()] {
+            Highlight::None => "",
+            Highlight::Ending => ", style=filled, fillcolor=palegreen",
+            Highlight::Unreachable => ", style=\"filled,dashed\", fillcolor=lightgray",
+        };
+        let bold = if s.id == story.start() { ", penwidth=2" } else { "" };
+        dot += &format!(
+            "    {} [label=\"{}\"{}{}];\n",
+            dot_string(&s.id),
+            label,
+            style,
+            bold
+        );
+    }
+    for target in graph.missing.iter() {
+        let style = if options.color_broken_links {
+            ", color=red, fontcolor=red, style=dashed"
+        } else {
+            ""
+        };
+        dot += &format!(
+            "    {} [label={}{}];\n",
+            dot_string(&format!("missing: {}", target)),
+            dot_string(&format!("{} (missing)", target)),
+            style
+        );
+    }
+    for s in graph.slides.iter() {
+        for a in s.actions.iter() {
+            let broken = graph.missing.contains(&a.target_slide.as_str());
+            let target = if broken {
+                format!("missing: {}", a.target_slide)
+            } else {
+                a.target_slide.clone()
+            };
+            let style = if broken && options.color_broken_links {
+                ", color=red, fontcolor=red"
+            } else {
+                ""
+            };
+            dot += &format!(
+                "    {} -> {} [label={}{}];\n",
+                dot_string(&s.id),
+                dot_string(&target),
+                dot_string(&a.text),
+                style
+            );
+        }
+    }
+    dot += "}\n";
+    dot
+}
+
+/// Mermaid labels can not hold quotes or angle brackets as they are.
+fn mermaid_escape(text: &str) -> String {
+    text.replace('"', "#quot;")
+        .replace('<', "#lt;")
+        .replace('>', "#gt;")
+}
+
+fn mermaid_string(text: &str) -> String {
+    format!("\"{}\"", mermaid_escape(text))
+}
+
+pub fn to_mermaid(story: &Story, options: &GraphOptions) -> String {
+    let graph = Graph::new(story, options);
+    // Slide ids can be anything an older file named a slide, so nodes get
+    // ids of their own.
+    let mut ids: HashMap<&str, String> = HashMap::new();
+    let mut mermaid = String::from("flowchart LR\n");
+    for (i, s) in graph.slides.iter().enumerate() {
+        let id = format!("s{}", i);
+        let label = node_label(story, s)
+            .iter()
+            .map(|l| mermaid_escape(l))
+            .collect::<Vec<_>>()
+            .join("<br/>");
+        mermaid += &format!("    {}[\"{}\"]\n", id, label);
+        ids.insert(s.id.as_str(), id);
+    }
+    for (i, target) in graph.missing.iter().enumerate() {
+        let id = format!("m{}", i);
+        mermaid += &format!(
+            "    {}[{}]\n",
+            id,
+            mermaid_string(&format!("{} (missing)", target))
+        );
+        ids.insert(*target, id);
+    }
+
+    let mut broken_edges = vec![];
+    let mut edge = 0;
+    for s in graph.slides.iter() {
+        for a in s.actions.iter() {
+            if graph.missing.contains(&a.target_slide.as_str()) {
+                broken_edges.push(edge);
+            }
+            mermaid += &format!(
+                "    {} -->|{}| {}\n",
+                ids[s.id.as_str()],
+                mermaid_string(&a.text),
+                ids[a.target_slide.as_str()]
+            );
+            edge += 1;
+        }
+    }
+
+    mermaid += "    classDef ending fill:#cfc,stroke:#393;\n";
+    mermaid += "    classDef unreachable fill:#ddd,stroke:#999,stroke-dasharray: 5 5;\n";
+    mermaid += "    classDef broken fill:#fcc,stroke:#f00,stroke-dasharray: 5 5;\n";
+    for s in graph.slides.iter() {
+        let class = match graph.highlights[s.id.as_str()] {
+            Highlight::None => continue,
+            Highlight::Ending => "ending",
+            Highlight::Unreachable => "unreachable",
+        };
+        mermaid += &format!("    class {} {};\n", ids[s.id.as_str()], class);
+    }
+    if options.color_broken_links {
+        for target in graph.missing.iter() {
+            mermaid += &format!("    class {} broken;\n", ids[target]);
+        }
+        for edge in broken_edges {
+            mermaid += &format!("    linkStyle {} stroke:#f00,color:#f00;\n", edge);
+        }
+    }
+    mermaid
+}
+
+pub fn render(story: &Story, format: GraphFormat, options: &GraphOptions) -> String {
+    match format {
+        GraphFormat::Dot => to_dot(story, options),
+        GraphFormat::Mermaid => to_mermaid(story, options),
+    }
+}
+
+/// Writes the graph next to the HTML export, giving where it ended up.
+pub fn write_graph(
+    project: &Project,
+    story: &Story,
+    format: GraphFormat,
+    options: &GraphOptions,
+) -> Result<PathBuf, PersistenceError> {
+    let dir = project.path(EXPORT_DIR);
+    fs::create_dir_all(&dir).map_err(|e| PersistenceError::io(&dir, e))?;
+    let path = dir.join(format.file_name());
+    write_text(&path, &render(story, format, options))?;
+    Ok(path)
+}
+
+/// Headless `--dot` and `--mermaid` modes, print the graph to stdout with the
+/// highlights the arguments leave on. Returns the exit code.
+pub fn run_graph_export(project: &Project, format: GraphFormat) -> i32 {
+    match Story::load(project) {
+        Ok(story) => {
+            print!("{}", render(&story, format, &GraphOptions::from_args()));
+            0
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            2
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::story::fixtures::{action, slide};
+
+    /// Goes from the start to an ending and to a slide that does not exist,
+    /// and has a slide nothing leads to.
+    fn story() -> Story {
+        let mut story = Story::default();
+        story.manifest.start_slide = "a".into();
+        story.slides = vec![
+            slide(
+                "a",
+                vec![action("Go", "b", vec![]), action("Jump \"up\"", "gone", vec![])],
+            ),
+            Slide {
+                ending: true,
+                ..slide("b", vec![])
+            },
+            slide("c", vec![action("Back", "a", vec![])]),
+        ];
+        story
+    }
+
+    fn plain() -> GraphOptions {
+        GraphOptions {
+            color_endings: false,
+            color_unreachable: false,
+            color_broken_links: false,
+        }
+    }
+
+    #[test]
+    fn writes_dot() {
+        assert_eq!(
+            to_dot(&story(), &GraphOptions::default()),
+            r#"digraph story {
+    rankdir=LR;
+    node [shape=box];
+    "a" [label="A", penwidth=2];
+    "b" [label="B", style=filled, fillcolor=palegreen];
+    "c" [label="C", style="filled,dashed", fillcolor=lightgray];
+    "missing: gone" [label="gone (missing)", color=red, fontcolor=red, style=dashed];
+    "a" -> "b" [label="Go"];
+    "a" -> "missing: gone" [label="Jump \"up\"", color=red, fontcolor=red];
+    "c" -> "a" [label="Back"];
+}
+"#
+        );
+        let dot = to_dot(&story(), &plain());
+        assert!(!dot.contains("fillcolor") && !dot.contains("red"));
+    }
+
+    #[test]
+    fn writes_mermaid() {
+        assert_eq!(
+            to_mermaid(&story(), &GraphOptions::default()),
+            r#"flowchart LR
+    s0["A"]
+    s1["B"]
+    s2["C"]
+    m0["gone (missing)"]
+    s0 -->|"Go"| s1
+    s0 -->|"Jump #quot;up#quot;"| m0
+    s2 -->|"Back"| s0
+    classDef ending fill:#cfc,stroke:#393;
+    classDef unreachable fill:#ddd,stroke:#999,stroke-dasharray: 5 5;
+    classDef broken fill:#fcc,stroke:#f00,stroke-dasharray: 5 5;
+    class s1 ending;
+    class s2 unreachable;
+    class m0 broken;
+    linkStyle 1 stroke:#f00,color:#f00;
+"#
+        );
+        let mermaid = to_mermaid(&story(), &plain());
+        assert!(!mermaid.contains("    class ") && !mermaid.contains("linkStyle"));
+    }
+}
@@ -0,0 +1,290 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_egui::{
+    egui::{self, Align2, Color32, Pos2, Rect, Sense, Stroke, TextStyle, Vec2},
+    EguiContext,
+};
+
+use crate::{
+    editors::SlideEditor,
+    images::Background,
+    graph_export::{self, GraphFormat, GraphOptions},
+    manifest::StoryManifest,
+    model::{Action, CrudEvent, EditorsOpen, Slide},
+    persistence::PersistenceResult,
+    project::Project,
+    story::Story,
+};
+
+const NODE_WIDTH: f32 = 140.;
+const NODE_HEIGHT: f32 = 36.;
+const PORT_RADIUS: f32 = 5.;
+
+/// Pannable, zoomable node graph of the slides, toggled from the slide list.
+pub(crate) struct GraphView {
+    pub(crate) open: bool,
+    pan: Vec2,
+    zoom: f32,
+    /// Id of the slide being dragged around and its position in graph
+    /// coordinates, only written back to the slide on release.
+    moving: Option<(String, Pos2)>,
+    /// Id of the slide an action is being dragged out of.
+    linking: Option<String>,
+    export_options: GraphOptions,
+}
+
+impl Default for GraphView {
+    fn default() -> Self {
+        Self {
+            open: false,
+            pan: Vec2::new(20., 20.),
+            zoom: 1.0,
+            moving: None,
+            linking: None,
+            export_options: GraphOptions::default(),
+        }
+    }
+}
+
+/// Where a slide sits in graph coordinates, slides that were never placed are
+/// laid out on a grid in query order.
+fn slide_position(slide: &Slide, index: usize) -> Pos2 {
+    match slide.position {
+        Some((x, y)) => Pos2::new(x as f32, y as f32),
+        None => Pos2::new(
+            (index % 5) as f32 * (NODE_WIDTH + 40.),
+            (index / 5) as f32 * (NODE_HEIGHT + 60.),
+        ),
+    }
+}
+
+impl GraphView {
+    fn to_screen(&self, canvas: Rect, p: Pos2) -> Pos2 {
+        canvas.min + self.pan + p.to_vec2() * self.zoom
+    }
+
+    pub(crate) fn render(
+        egui_context: ResMut<EguiContext>,
+        mut view: ResMut<GraphView>,
+        slides: Query<&Slide>,
+        backgrounds: Query<&Background>,
+        manifest: Res<StoryManifest>,
+        project: Res<Project>,
+        mut slide_events: EventWriter<CrudEvent<Slide>>,
+        mut results: EventWriter<PersistenceResult>,
+        mut commands: Commands,
+        editors_open: Res<EditorsOpen>,
+    ) {
+        if !editors_open.0 || !view.open {
+            return;
+        }
+        let view = &mut *view;
+        let mut open = true;
+        egui::Window::new("Story graph")
+            .default_size([800., 500.])
+            .open(&mut open)
+            .show(egui_context.ctx(), |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Drag the background to pan, scroll to zoom, drag from a node's dot to add an action.");
+                    if ui.small_button("Reset view").clicked() {
+                        view.pan = GraphView::default().pan;
+                        view.zoom = 1.0;
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let options = &mut view.export_options;
+                    ui.label("Export coloring");
+                    ui.checkbox(&mut options.color_endings, "endings");
+                    ui.checkbox(&mut options.color_unreachable, "unreachable");
+                    ui.checkbox(&mut options.color_broken_links, "broken links");
+                    for (format, label) in [
+                        (GraphFormat::Dot, "Export DOT"),
+                        (GraphFormat::Mermaid, "Export Mermaid"),
+                    ]
+                    .iter()
+                    {
+                        let clicked = ui
+                            .small_button(*label)
+                            .on_hover_text("Written to the export folder and copied to the clipboard")
+                            .clicked();
+                        if !clicked {
+                            continue;
+                        }
+                        let story = Story {
+                            slides: slides.iter().cloned().collect(),
+                            backgrounds: backgrounds.iter().cloned().collect(),
+                            manifest: manifest.clone(),
+                            ..Default::default()
+                        };
+                        ui.output().copied_text = graph_export::render(&story, *format, options);
+                        let written = graph_export::write_graph(&project, &story, *format, options);
+                        results.send(match written {
+                            Ok(path) => PersistenceResult::Saved {
+                                path,
+                                count: story.slides.len(),
+                            },
+                            Err(e) => PersistenceResult::Failed(e),
+                        });
+                    }
+                });
+                let (canvas, painter) =
+                    ui.allocate_painter(ui.available_size(), Sense::click_and_drag());
+                let rect = canvas.rect;
+
+                let mut positions: Vec<(&Slide, Pos2)> = slides
+                    .iter()
+                    .enumerate()
+                    .map(|(i, s)| (s, slide_position(s, i)))
+                    .collect();
+                if let Some((id, p)) = &view.moving {
+                    for (s, pos) in positions.iter_mut() {
+                        if s.id == *id {
+                            *pos = *p;
+                        }
+                    }
+                }
+                let node_size = Vec2::new(NODE_WIDTH, NODE_HEIGHT) * view.zoom;
+                let node_rects: HashMap<&str, Rect> = positions
+                    .iter()
+                    .map(|(s, p)| {
+                        (
+                            s.id.as_str(),
+                            Rect::from_min_size(view.to_screen(rect, *p), node_size),
+                        )
+                    })
+                    .collect();
+
+                for (s, _) in positions.iter() {
+                    let from = node_rects[s.id.as_str()];
+                    for (i, a) in s.actions.iter().enumerate() {
+                        let to = match node_rects.get(a.target_slide.as_str()) {
+                            None => continue,
+                            Some(r) => *r,
+                        };
+                        let stroke = Stroke::new(1.0, Color32::GRAY);
+                        let label_pos = if from == to {
+                            painter.circle_stroke(
+                                from.right_top(),
+                                NODE_HEIGHT * 0.4 * view.zoom,
+                                stroke,
+                            );
+                            from.right_top() + Vec2::new(0., -12. * (i as f32 + 1.))
+                        } else {
+                            let dir = (to.center() - from.center()).normalized();
+                            let start = from.center() + dir * node_size.y * 0.5;
+                            let end = to.center() - dir * node_size.y * 0.5;
+                            painter.line_segment([start, end], stroke);
+                            painter.arrow(end - dir * 10., dir * 10., stroke);
+                            let normal = Vec2::new(-dir.y, dir.x);
+                            start + (end - start) * 0.5 + normal * 12. * i as f32
+                        };
+                        painter.text(
+                            label_pos,
+                            Align2::CENTER_CENTER,
+                            &a.text,
+                            TextStyle::Small,
+                            Color32::LIGHT_GRAY,
+                        );
+                    }
+                }
+
+                let pointer = ui.input().pointer.hover_pos();
+                let mut node_active = false;
+                for (s, pos) in positions.iter() {
+                    let node_rect = node_rects[s.id.as_str()];
+                    let node = ui.interact(
+                        node_rect,
+                        egui::Id::new(("graph_node", &s.id)),
+                        Sense::click_and_drag(),
+                    );
+                    if node.dragged() {
+                        node_active = true;
+                        view.moving = Some((s.id.clone(), *pos + node.drag_delta() / view.zoom));
+                    }
+                    if node.drag_released() {
+                        let mut moved = (*s).clone();
+                        moved.position = Some((pos.x.round() as i32, pos.y.round() as i32));
+                        slide_events.send(CrudEvent::Updated(moved));
+                        view.moving = None;
+                    }
+                    if node.clicked() {
+                        commands.spawn().insert(SlideEditor::new_for(&s.id));
+                    }
+
+                    let stroke_color = if s.id == manifest.start_slide {
+                        Color32::GREEN
+                    } else if node.hovered() {
+                        Color32::WHITE
+                    } else {
+                        Color32::GRAY
+                    };
+                    painter.rect_filled(node_rect, 4., Color32::from_gray(40));
+                    painter.rect_stroke(node_rect, 4., Stroke::new(1.0, stroke_color));
+                    painter.text(
+                        node_rect.center(),
+                        Align2::CENTER_CENTER,
+                        &s.name,
+                        TextStyle::Body,
+                        Color32::WHITE,
+                    );
+
+                    let port_center = node_rect.right_center();
+                    let port = ui.interact(
+                        Rect::from_center_size(port_center, Vec2::splat(PORT_RADIUS * 3.)),
+                        egui::Id::new(("graph_port", &s.id)),
+                        Sense::drag(),
+                    );
+                    painter.circle_filled(port_center, PORT_RADIUS, Color32::LIGHT_BLUE);
+                    if port.dragged() {
+                        node_active = true;
+                        view.linking = Some(s.id.clone());
+                        if let Some(p) = pointer {
+                            painter.line_segment(
+                                [port_center, p],
+                                Stroke::new(1.0, Color32::LIGHT_BLUE),
+                            );
+                        }
+                    }
+                    if port.drag_released() {
+                        view.linking = None;
+                        let target = pointer.and_then(|p| {
+                            positions
+                                .iter()
+                                .find(|(t, _)| node_rects[t.id.as_str()].contains(p))
+                                .map(|(t, _)| *t)
+                        });
+                        if let Some(target) = target {
+                            let mut linked = (*s).clone();
+                            linked.actions.push(Action {
+                                text: format!("Go to {}", target.name),
+                                target_slide: target.id.clone(),
+                                ..Default::default()
+                            });
+                            slide_events.send(CrudEvent::Updated(linked));
+                            commands.spawn().insert(SlideEditor::new_for(&s.id));
+                        }
+                    }
+                }
+
+                if !node_active && view.moving.is_none() && canvas.dragged() {
+                    view.pan += canvas.drag_delta();
+                }
+                if canvas.hovered() {
+                    let scroll = ui.input().scroll_delta.y;
+                    if scroll != 0. {
+                        let old_zoom = view.zoom;
+                        view.zoom = (view.zoom * (1.0 + scroll * 0.002)).max(0.2).min(3.0);
+                        // Keep the point under the cursor where it is.
+                        if let Some(p) = pointer {
+                            let anchor = p - rect.min - view.pan;
+                            view.pan -= anchor * (view.zoom / old_zoom - 1.0);
+                        }
+                    }
+                }
+            });
+        if !open {
+            view.open = false;
+        }
+    }
+}
@@ -1,34 +1,62 @@
 use serde::*;
-use crate::model::EditorsOpen;
 use std::{
+    fs,
     io::Read,
-    path::Path,
-    thread::{spawn, JoinHandle},
+    path::{Component, Path, PathBuf},
+    time::Duration,
+};
+#[cfg(feature = "editor")]
+use std::{
+    sync::mpsc::{channel, Receiver, Sender},
+    thread::{sleep, spawn, JoinHandle},
 };
 
+#[cfg(feature = "editor")]
 use bevy::{
     prelude::*,
     render::texture::{Extent3d, ImageType, TextureDimension, TextureFormat},
 };
+#[cfg(feature = "editor")]
 use bevy_egui::{egui, EguiContext};
-use image::{GenericImageView, RgbaImage};
-
-const WIDTH: u32 = 128;
-const HEIGHT: u32 = WIDTH / 4;
-
-fn load_from_bytes(bytes: &[u8]) -> (RgbaImage, Texture) {
-    let reader = image::io::Reader::new(std::io::Cursor::new(bytes))
-        .with_guessed_format()
-        .expect("Cursor never fails");
-    let image = reader
-        .decode()
-        .expect("Failed to decode image")
-        .resize_to_fill(WIDTH, HEIGHT * 2, image::imageops::FilterType::Nearest)
-        .resize_exact(WIDTH, HEIGHT, image::imageops::FilterType::Nearest)
-        .to_rgba8();
+#[cfg(feature = "editor")]
+use image::GenericImageView;
+use image::RgbaImage;
+use tracing::{info, warn};
+
+use crate::{
+    ascii::decode_image,
+    persistence::{id_from_name, write_bytes, Persistable},
+    project::Project,
+};
+#[cfg(feature = "editor")]
+use crate::{
+    ascii::{
+        convert_image_to_ascii, convert_image_to_colored_ascii, placeholder_ascii, ColoredAscii,
+        HEIGHT, WIDTH,
+    },
+    editors::RenameDialog,
+    model::{new_id, CrudEvent, EditorsOpen},
+    persistence::PersistenceEvent,
+};
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(20);
+#[cfg(feature = "editor")]
+const FETCH_ATTEMPTS: u32 = 3;
+#[cfg(feature = "editor")]
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Loads the background right away on the calling thread, for use outside
+/// of the app where there is no one to wait for a worker.
+pub fn load_background_image(project: &Project, bg: &Background) -> Result<RgbaImage, String> {
+    fetch_decoded(project, &bg.url, decode_image)?
+}
+
+#[cfg(feature = "editor")]
+fn load_from_bytes(bytes: &[u8]) -> Result<(RgbaImage, Texture), String> {
+    let image = decode_image(bytes)?;
     let data: Vec<u8> = image.pixels().map(|p| &p.0).flatten().cloned().collect();
     let size = image.dimensions();
-    (
+    Ok((
         image,
         Texture::new_fill(
             Extent3d::new(size.0, size.1, 1),
@@ -36,44 +64,153 @@ fn load_from_bytes(bytes: &[u8]) -> (RgbaImage, Texture) {
             &data,
             TextureFormat::Rgba8UnormSrgb,
         ),
-    )
+    ))
 }
 
-fn load_from_response(response: ureq::Response) -> (RgbaImage, Texture) {
+/// How much is read of responses that do not tell their length.
+const UNSIZED_RESPONSE_LIMIT: u64 = 64 * 1024 * 1024;
+
+fn read_response(response: ureq::Response) -> Result<Vec<u8>, String> {
     let len = response
         .header("Content-Length")
-        .and_then(|s| s.parse::<usize>().ok())
-        .unwrap_or_default();
-    let mut bytes: Vec<u8> = Vec::with_capacity(len);
+        .and_then(|s| s.parse::<usize>().ok());
+    let limit = len.map_or(UNSIZED_RESPONSE_LIMIT, |len| len as u64 * 2);
+    let mut bytes: Vec<u8> = Vec::with_capacity(len.unwrap_or_default());
     response
         .into_reader()
-        .take(len as u64 * 2)
+        .take(limit + 1)
         .read_to_end(&mut bytes)
-        .expect("Could not read to end");
-    load_from_bytes(&bytes)
+        .map_err(|e| format!("Could not read the response: {}", e))?;
+    if bytes.len() as u64 > limit {
+        return Err(format!("The response is bigger than {} bytes", limit));
+    }
+    Ok(bytes)
 }
 
-use std::sync::mpsc::{channel, Receiver, Sender};
+/// Within the project directory, so stories do not share downloads.
+pub(crate) const CACHE_DIR: &str = "image_cache";
 
-use crate::{
-    editors::RenameDialog,
-    model::CrudEvent,
-    persistence::{Persistable, PersistenceEvent},
-};
+fn is_remote(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
 
-fn request_image(url: String, sender: Sender<(String, RgbaImage, Texture)>) -> JoinHandle<()> {
+/// FNV-1a, stable across runs and Rust versions unlike the std hasher.
+fn url_hash(url: &str) -> u64 {
+    url.bytes().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn cache_path(project: &Project, url: &str) -> PathBuf {
+    project.path(CACHE_DIR).join(format!("{:016x}", url_hash(url)))
+}
+
+/// Drops the cached copy of a remote image, so the next request downloads it.
+pub fn forget_cached(project: &Project, url: &str) {
+    let path = cache_path(project, url);
+    if path.exists() {
+        if let Err(e) = fs::remove_file(&path) {
+            warn!("Could not remove {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Decodes the bytes behind a background url: `file://` urls and plain paths
+/// (relative to the project directory) are read from disk, remote images go
+/// through the cache. The outer error is for fetching, the inner one for
+/// decoding, and only downloads that decode are cached.
+fn fetch_decoded<T>(
+    project: &Project,
+    url: &str,
+    decode: impl FnOnce(&[u8]) -> Result<T, String>,
+) -> Result<Result<T, String>, String> {
+    let read_file = |path: &str| {
+        let path = project.path(path);
+        fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e))
+    };
+    if let Some(path) = url.strip_prefix("file://") {
+        return read_file(path).map(|bytes| decode(&bytes));
+    }
+    if !is_remote(url) {
+        return read_file(url).map(|bytes| decode(&bytes));
+    }
+    let cached = cache_path(project, url);
+    if let Ok(bytes) = fs::read(&cached) {
+        info!("Loading {} from the cache", url);
+        return Ok(decode(&bytes));
+    }
+    info!("Requesting image from {}", url);
+    let agent = ureq::AgentBuilder::new().timeout(FETCH_TIMEOUT).build();
+    let response = agent
+        .get(url)
+        .call()
+        .map_err(|e| format!("Request failed: {}", e))?;
+    let bytes = read_response(response)?;
+    let decoded = decode(&bytes);
+    if decoded.is_ok() {
+        let written = fs::create_dir_all(project.path(CACHE_DIR))
+            .map_err(|e| e.to_string())
+            .and_then(|_| write_bytes(&cached, &bytes).map_err(|e| e.to_string()));
+        if let Err(e) = written {
+            warn!("Could not cache {}: {}", url, e);
+        }
+    }
+    Ok(decoded)
+}
+
+/// Progress of a worker thread loading the image behind a url.
+#[cfg(feature = "editor")]
+enum ImageLoad {
+    Retrying { attempt: u32, error: String },
+    Loaded(RgbaImage, Texture),
+    Failed(String),
+}
+
+/// Fetches on a worker thread, retrying failed fetches with a doubling delay.
+/// Images that do not decode are not retried, they would fail again.
+#[cfg(feature = "editor")]
+fn request_image(
+    project: Project,
+    url: String,
+    sender: Sender<(String, ImageLoad)>,
+) -> JoinHandle<()> {
     spawn(move || {
-        info!("Requesting image from {}", url);
-        let response = ureq::get(&url).call().expect("Failed to request image");
-        let (i, t) = load_from_response(response);
-        sender
-            .send((url, i, t))
-            .expect("Failed to send the loaded image");
+        let mut delay = FIRST_RETRY_DELAY;
+        let mut attempt = 1;
+        let result = loop {
+            match fetch_decoded(&project, &url, load_from_bytes) {
+                Ok(decoded) => break decoded,
+                Err(error) if attempt < FETCH_ATTEMPTS => {
+                    warn!("Loading {} failed, retrying in {:?}: {}", url, delay, error);
+                    attempt += 1;
+                    // The receiver is gone when the app is closing.
+                    if sender
+                        .send((url.clone(), ImageLoad::Retrying { attempt, error }))
+                        .is_err()
+                    {
+                        return;
+                    }
+                    sleep(delay);
+                    delay *= 2;
+                }
+                Err(error) => break Err(error),
+            }
+        };
+        let load = match result {
+            Ok((i, t)) => ImageLoad::Loaded(i, t),
+            Err(error) => {
+                warn!("Could not load {}: {}", url, error);
+                ImageLoad::Failed(error)
+            }
+        };
+        sender.send((url, load)).ok();
     })
 }
 
+#[cfg(feature = "editor")]
 pub struct ImagesPlugin;
 
+#[cfg(feature = "editor")]
 impl Plugin for ImagesPlugin {
     fn build(&self, builder: &mut AppBuilder) {
         let (sender, receiver) = channel();
@@ -86,23 +223,25 @@ impl Plugin for ImagesPlugin {
             })
             .add_plugin(crate::persistence::PersistencePlugin::<Background>::new())
             .add_plugin(crate::model::CrudPlugin::<Background>::new())
+            .add_plugin(crate::undo::UndoPlugin::<Background>::new())
             .add_system(receive_images.system())
             .add_system(RenameDialog::<Background>::render.system())
             .add_system(auto_request_images.system())
             .add_system(DeleteBgDialog::render.system())
             .add_system(BackgroundEditor::render.system())
-            .add_system(BackgroundEditor::handle_renames.system())
             .add_system(images.system());
     }
 }
 
+#[cfg(feature = "editor")]
 struct ImagesRes {
     url: String,
-    sender: Sender<(String, RgbaImage, Texture)>,
-    receiver: Receiver<(String, RgbaImage, Texture)>,
+    sender: Sender<(String, ImageLoad)>,
+    receiver: Receiver<(String, ImageLoad)>,
     next_egui_id: u64,
 }
 
+#[cfg(feature = "editor")]
 fn receive_images(
     mut egui_context: ResMut<EguiContext>,
     mut images: NonSendMut<ImagesRes>,
@@ -115,38 +254,92 @@ fn receive_images(
         ref mut next_egui_id,
         ..
     } = *images;
-    for (url, image, tex) in receiver.try_iter() {
-        let texture_handle = textures.add(tex);
-        egui_context.set_egui_texture(*next_egui_id, texture_handle.clone());
-
-        for (e, _bg) in backgrounds.iter().filter(|(_, bg)| bg.url == url) {
-            commands.entity(e).insert(BackgroundData {
-                image: image.clone(),
-                texture_handle: texture_handle.clone(),
-                ui_texture: egui::TextureId::User(*next_egui_id),
-            });
-        }
+    for (url, load) in receiver.try_iter() {
+        let matching = backgrounds.iter().filter(|(_, bg)| bg.url == url);
+        match load {
+            ImageLoad::Retrying { attempt, error } => {
+                for (e, _bg) in matching {
+                    commands
+                        .entity(e)
+                        .insert(LoadStatus::Retrying { attempt, error: error.clone() });
+                }
+            }
+            ImageLoad::Failed(error) => {
+                for (e, _bg) in matching {
+                    commands
+                        .entity(e)
+                        .remove::<BackgroundData>()
+                        .insert(LoadStatus::Failed(error.clone()));
+                }
+            }
+            ImageLoad::Loaded(image, tex) => {
+                let texture_handle = textures.add(tex);
+                egui_context.set_egui_texture(*next_egui_id, texture_handle.clone());
+
+                for (e, _bg) in matching {
+                    commands
+                        .entity(e)
+                        .insert(BackgroundData {
+                            image: image.clone(),
+                            texture_handle: texture_handle.clone(),
+                            ui_texture: egui::TextureId::User(*next_egui_id),
+                        })
+                        .insert(LoadStatus::Loaded);
+                }
 
-        *next_egui_id += 1;
+                *next_egui_id += 1;
+            }
+        }
     }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct Background {
+    /// What slides refer to the background by, files from before ids
+    /// existed are migrated to have the name as id.
+    id: String,
     name: String,
     url: String,
     color_channels: (usize, usize, usize),
 }
 
 impl Persistable for Background {
-    fn file_path() -> &'static Path {
-        Path::new("backgrounds.json")
+    fn file_name() -> &'static str {
+        "backgrounds.json"
     }
-    fn sortable_name<'a>(&'a self) -> &'a str {
+    fn sortable_name(&self) -> &str {
         &self.name
     }
+    fn migrate(from_version: u32, resource: &mut serde_json::Value) {
+        if from_version == 1 {
+            id_from_name(resource);
+        }
+    }
 }
+impl Background {
+    /// Weights of the red, green and blue channels when picking characters.
+    pub fn color_channels(&self) -> (usize, usize, usize) {
+        self.color_channels
+    }
+
+    /// Where the image is within the project directory, for images that are
+    /// neither downloaded nor somewhere else on disk.
+    pub fn path_in_project(&self) -> Option<&Path> {
+        if is_remote(&self.url) {
+            return None;
+        }
+        let path = Path::new(self.url.strip_prefix("file://").unwrap_or(&self.url));
+        let inside = path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+        Some(path).filter(|_| inside && !self.url.is_empty())
+    }
+}
+
 impl crate::model::Crudable for Background {
+    fn id(&self) -> String {
+        self.id.clone()
+    }
     fn name(&self) -> String {
         self.name.clone()
     }
@@ -158,34 +351,77 @@ impl crate::model::Crudable for Background {
     }
 }
 
+#[cfg(feature = "editor")]
 pub struct BackgroundData {
     image: RgbaImage,
     texture_handle: Handle<Texture>,
     ui_texture: egui::TextureId,
 }
 
-struct Requested;
+#[cfg(feature = "editor")]
+impl BackgroundData {
+    /// Different for every load, a refreshed image gets a new one.
+    pub fn texture_id(&self) -> egui::TextureId {
+        self.ui_texture
+    }
+}
+
+/// Where loading the image of a background is at, backgrounds without one
+/// have not been requested yet.
+#[cfg(feature = "editor")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadStatus {
+    Pending,
+    Retrying { attempt: u32, error: String },
+    Loaded,
+    Failed(String),
+}
+
+#[cfg(feature = "editor")]
+impl LoadStatus {
+    fn show(&self, ui: &mut egui::Ui) {
+        match self {
+            LoadStatus::Pending => ui.colored_label(egui::Color32::GRAY, "loading..."),
+            LoadStatus::Retrying { attempt, error } => ui.colored_label(
+                egui::Color32::YELLOW,
+                format!("retrying ({}/{})", attempt, FETCH_ATTEMPTS),
+            )
+            .on_hover_text(error),
+            LoadStatus::Loaded => return,
+            LoadStatus::Failed(error) => {
+                ui.colored_label(egui::Color32::RED, format!("failed: {}", error))
+            }
+        };
+    }
+}
 
+#[cfg(feature = "editor")]
 fn auto_request_images(
-    backgrounds: Query<(Entity, &Background), Without<Requested>>,
+    backgrounds: Query<(Entity, &Background), Without<LoadStatus>>,
     images: NonSendMut<ImagesRes>,
+    project: Res<Project>,
     mut commands: Commands,
 ) {
     for (e, bg) in backgrounds.iter() {
-        commands.entity(e).insert(Requested);
-        request_image(bg.url.clone(), images.sender.clone());
+        commands.entity(e).insert(LoadStatus::Pending);
+        request_image(project.clone(), bg.url.clone(), images.sender.clone());
     }
 }
 
+#[cfg(feature = "editor")]
 fn images(
     egui_context: ResMut<EguiContext>,
     mut images: NonSendMut<ImagesRes>,
     mut commands: Commands,
-    backgrounds: Query<(&Background, Option<&BackgroundData>)>,
+    backgrounds: Query<(Entity, &Background, Option<&BackgroundData>, Option<&LoadStatus>)>,
     mut bg_events: EventWriter<CrudEvent<Background>>,
     editors_open: Res<EditorsOpen>,
+    project: Res<Project>,
 ) {
-    let valid_bg_names: Vec<_> = backgrounds.iter().map(|(bg, _)| bg.name.clone()).collect();
+    let valid_bg_names: Vec<_> = backgrounds
+        .iter()
+        .map(|(_, bg, _, _)| bg.name.clone())
+        .collect();
     if !editors_open.0 {
         return;
     }
@@ -198,6 +434,7 @@ fn images(
                     .next()
                     .expect("Abusrd amount of badly named slides");
                 bg_events.send(CrudEvent::Created(Background {
+                    id: new_id("bg"),
                     name: new_name,
                     url: "https://img.freepik.com/free-photo/question-mark-icon-glow-dark-3d-illustration_103740-348.jpg?size=626&ext=jpg".into(),
                     color_channels: (255, 255, 255),
@@ -206,7 +443,7 @@ fn images(
         });
         ui.separator();
 
-        for (bg, bgd) in backgrounds.iter() {
+        for (bg_entity, bg, bgd, status) in backgrounds.iter() {
             // ui.label(format!(
             //     "size: {:?}, format: {:?}, len: {}",
             //     t.size,
@@ -220,21 +457,35 @@ fn images(
                 if let Some(bgd) = bgd {
                     ui.image(bgd.ui_texture, [WIDTH as f32, 2. * HEIGHT as f32]);
                 }
+                if let Some(status) = status {
+                    status.show(ui);
+                    if let LoadStatus::Failed(_) = status {
+                        if ui.small_button("retry").clicked() {
+                            // Downloads the image again, even one that did
+                            // not decode.
+                            forget_cached(&project, &bg.url);
+                            commands.entity(bg_entity).remove::<LoadStatus>();
+                        }
+                    }
+                }
                 if ui.button("edit").clicked() {
-                    commands.spawn().insert(BackgroundEditor::new_for(&bg.name));
+                    commands.spawn().insert(BackgroundEditor::new_for(&bg.id));
                 }
                 if ui.button("rename").clicked() {
                     commands.insert_resource(RenameDialog::new_for(bg));
                 }
                 if ui.button("delete").clicked() {
-                    commands.insert_resource(DeleteBgDialog(bg.name.clone()));
+                    commands.insert_resource(DeleteBgDialog(bg.id.clone()));
                 }
             });
         }
     });
 }
 
+/// Holds the id of the background to delete.
+#[cfg(feature = "editor")]
 struct DeleteBgDialog(String);
+#[cfg(feature = "editor")]
 impl DeleteBgDialog {
     fn render(
         egui_context: ResMut<EguiContext>,
@@ -242,6 +493,7 @@ impl DeleteBgDialog {
         mut slide_events: EventWriter<CrudEvent<Background>>,
         mut commands: Commands,
         slides: Query<&crate::model::Slide>,
+        backgrounds: Query<&Background>,
         editors_open: Res<EditorsOpen>,
     ) {
         if dialog.is_none() {
@@ -254,13 +506,17 @@ impl DeleteBgDialog {
             .filter(|s| s.background == dialog.0)
             .map(|s| s.name.clone())
             .collect();
+        let name = backgrounds
+            .iter()
+            .find(|b| b.id == dialog.0)
+            .map_or(dialog.0.clone(), |b| b.name.clone());
 
         if !editors_open.0 {
             return;
         }
         egui::Window::new("Delete background").show(egui_context.ctx(), |ui| {
             ui.horizontal(|ui| {
-                ui.label(format!("Goging to delete \"{}\"", dialog.0));
+                ui.label(format!("Goging to delete \"{}\"", name));
             });
             ui.horizontal(|ui| {
                 if ui.button("Cancel").clicked() {
@@ -282,46 +538,33 @@ impl DeleteBgDialog {
     }
 }
 
+#[cfg(feature = "editor")]
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 struct BackgroundEditor {
+    /// Id of the background being edited.
     target: String,
 }
 
+#[cfg(feature = "editor")]
 impl BackgroundEditor {
     fn new_for(target: &str) -> Self {
         Self {
             target: target.into(),
         }
     }
-    fn handle_renames(
-        mut editors: Query<&mut Self>,
-        mut slide_events: EventReader<CrudEvent<Background>>,
-    ) {
-        for ev in slide_events.iter() {
-            match ev {
-                CrudEvent::Renamed(old_name, new_name) => {
-                    for mut e in editors.iter_mut() {
-                        if e.target == *old_name {
-                            e.target = new_name.clone();
-                        }
-                    }
-                }
-                _ => {}
-            }
-        }
-    }
     fn render(
         egui_context: ResMut<EguiContext>,
         mut editors: Query<(Entity, &mut Self)>,
-        backgrounds: Query<(Entity, &Background, &BackgroundData)>,
+        backgrounds: Query<(Entity, &Background, Option<&BackgroundData>, Option<&LoadStatus>)>,
         mut bg_events: EventWriter<CrudEvent<Background>>,
         mut commands: Commands,
         editors_open: Res<EditorsOpen>,
+        project: Res<Project>,
     ) {
         for (editor_id, mut editor) in editors.iter_mut() {
-            let (bg_entity, saved, bdata) = match backgrounds
+            let (bg_entity, saved, bdata, status) = match backgrounds
                 .iter()
-                .filter(|(_, b, _)| b.name == editor.target)
+                .filter(|(_, b, _, _)| b.id == editor.target)
                 .next()
             {
                 None => {
@@ -349,14 +592,22 @@ impl BackgroundEditor {
                         }
                     });
                     ui.horizontal(|ui| {
-                        ui.label("URL:");
+                        ui.label("URL or path:");
                         ui.text_edit_multiline(&mut unsaved.url);
                         if ui.small_button("refresh").clicked() {
-                            commands.entity(bg_entity).remove::<Requested>();
+                            forget_cached(&project, &saved.url);
+                            commands.entity(bg_entity).remove::<LoadStatus>();
+                        }
+                        if let Some(status) = status {
+                            status.show(ui);
                         }
                     });
                     ui.separator();
-                    let mut ascii = convert_background_to_ascii(saved, bdata, 1.0);
+                    let mut ascii = match (bdata, status) {
+                        (Some(bdata), _) => convert_background_to_ascii(saved, bdata, 1.0),
+                        (None, Some(LoadStatus::Failed(error))) => placeholder_ascii(error),
+                        (None, _) => placeholder_ascii("Loading..."),
+                    };
                     ui.add(
                         egui::TextEdit::multiline(&mut ascii)
                             .text_style(egui::TextStyle::Monospace)
@@ -373,45 +624,18 @@ impl BackgroundEditor {
     }
 }
 
+#[cfg(feature = "editor")]
 pub fn convert_background_to_ascii(bg: &Background, bgd: &BackgroundData, alpha: f32) -> String {
     info!("Converting {} to ascii", bg.name);
-    bgd.image
-        .pixels()
-        .map(|p| pixel_to_intensity(bg, p))
-        .map(|i| (i as f32 * alpha) as u8)
-        .map(|i| intensity_to_ascii(i))
-        .collect::<Vec<_>>()
-        .chunks(WIDTH as usize)
-        .map(|c| c.into_iter().cloned().collect::<String>() + "\n")
-        .collect()
+    convert_image_to_ascii(bg, &bgd.image, alpha)
 }
 
-fn pixel_to_intensity(bg: &Background, p: &image::Rgba<u8>) -> u8 {
-    let p = p.0;
-    let cc = bg.color_channels;
-    let (r, g, b) = (p[0] as usize, p[1] as usize, p[2] as usize);
-    (if cc.0 == cc.1 && cc.1 == cc.2 {
-        (r + g + b) / 3
-    } else {
-        (r * cc.0 + g * cc.1 + b * cc.2) / (cc.0 + cc.1 + cc.2)
-    }) as u8
-}
-
-// Copied from edelsonc/asciify
-fn intensity_to_ascii(value: u8) -> &'static str {
-    let ascii_chars = [
-        ".", "^", ",", ":", "_", "=", "~", "+", "O", "o", "*", "#", "&", "%", "B", "@", "$",
-    ];
-
-    let n_chars = ascii_chars.len() as u8;
-    let step = 255u8 / n_chars;
-    for i in 1..(n_chars - 1) {
-        let comp = &step * i;
-        if value < comp {
-            let idx = (i - 1) as usize;
-            return ascii_chars[idx];
-        }
-    }
-
-    ascii_chars[(n_chars - 1) as usize]
+#[cfg(feature = "editor")]
+pub fn convert_background_to_colored_ascii(
+    bg: &Background,
+    bgd: &BackgroundData,
+    alpha: f32,
+) -> ColoredAscii {
+    info!("Converting {} to colored ascii", bg.name);
+    convert_image_to_colored_ascii(bg, &bgd.image, alpha)
 }
@@ -0,0 +1,302 @@
+use serde::*;
+
+#[cfg(feature = "editor")]
+use bevy::prelude::*;
+#[cfg(feature = "editor")]
+use bevy_egui::{egui, EguiContext};
+
+use crate::{
+    game_state::parse_condition,
+    model::{Crudable, Slide},
+    persistence::Persistable,
+};
+#[cfg(feature = "editor")]
+use crate::{
+    editors::RenameDialog,
+    game_state::{rename_item_in_condition, Effect, GameState},
+    model::{CrudEvent, EditorsOpen},
+};
+
+#[cfg(feature = "editor")]
+pub struct ItemsPlugin;
+
+#[cfg(feature = "editor")]
+impl Plugin for ItemsPlugin {
+    fn build(&self, builder: &mut AppBuilder) {
+        builder
+            .add_plugin(crate::persistence::PersistencePlugin::<Item>::new())
+            .add_plugin(crate::model::CrudPlugin::<Item>::new())
+            .add_plugin(crate::undo::UndoPlugin::<Item>::new())
+            .add_system(RenameDialog::<Item>::render.system())
+            .add_system(DeleteItemDialog::render.system())
+            .add_system(ItemEditor::render.system())
+            .add_system(ItemEditor::handle_renames.system())
+            .add_system(update_references_to_items.system())
+            .add_system(items.system());
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct Item {
+    pub name: String,
+    pub description: String,
+    /// Small ASCII drawing shown in the inventory, may be empty.
+    #[serde(default)]
+    pub icon: String,
+}
+
+impl Item {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            description: String::new(),
+            icon: String::new(),
+        }
+    }
+}
+
+impl Persistable for Item {
+    fn file_name() -> &'static str {
+        "items.json"
+    }
+    fn sortable_name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Crudable for Item {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+    fn set_name(&mut self, new_name: String) {
+        self.name = new_name;
+    }
+    fn default_name_prefix() -> &'static str {
+        "item"
+    }
+}
+
+/// Whether any effect or condition of the slide mentions the item.
+pub fn slide_references_item(slide: &Slide, item: &str) -> bool {
+    slide.effects.iter().any(|e| e.item() == Some(item))
+        || slide.actions.iter().any(|a| {
+            a.effects.iter().any(|e| e.item() == Some(item))
+                || parse_condition(&a.condition)
+                    .map(|c| c.mentions_item(item))
+                    .unwrap_or(false)
+        })
+}
+
+#[cfg(feature = "editor")]
+fn rename_in_effects(effects: &mut [Effect], old_name: &str, new_name: &str) {
+    for e in effects.iter_mut() {
+        if e.item() == Some(old_name) {
+            *e.subject_mut() = new_name.into();
+        }
+    }
+}
+
+#[cfg(feature = "editor")]
+fn update_references_to_items(
+    mut events: EventReader<CrudEvent<Item>>,
+    mut query: Query<&mut Slide>,
+    mut game_state: ResMut<GameState>,
+) {
+    for e in events.iter() {
+        if let CrudEvent::Renamed(old_name, new_name) = e {
+            for mut s in query.iter_mut() {
+                if !slide_references_item(&s, old_name) {
+                    continue;
+                }
+                rename_in_effects(&mut s.effects, old_name, new_name);
+                for a in s.actions.iter_mut() {
+                    rename_in_effects(&mut a.effects, old_name, new_name);
+                    a.condition = rename_item_in_condition(&a.condition, old_name, new_name);
+                }
+            }
+            for i in game_state.inventory.iter_mut() {
+                if i == old_name {
+                    *i = new_name.clone();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "editor")]
+fn items(
+    egui_context: ResMut<EguiContext>,
+    mut commands: Commands,
+    items: Query<&Item>,
+    mut item_events: EventWriter<CrudEvent<Item>>,
+    editors_open: Res<EditorsOpen>,
+) {
+    let valid_item_names: Vec<_> = items.iter().map(|i| i.name.clone()).collect();
+    if !editors_open.0 {
+        return;
+    }
+    egui::Window::new("Items").show(egui_context.ctx(), |ui| {
+        ui.horizontal(|ui| {
+            if ui.button("Add New").clicked() {
+                let new_name = (0..10000)
+                    .map(|n| format!("item{}", n))
+                    .find(|name| !valid_item_names.contains(name))
+                    .expect("Absurd amount of badly named items");
+                item_events.send(CrudEvent::Created(Item::new(new_name.clone())));
+                commands.spawn().insert(ItemEditor::new_for(&new_name));
+            }
+        });
+        ui.separator();
+
+        for item in items.iter() {
+            ui.horizontal(|ui| {
+                if !item.icon.is_empty() {
+                    ui.monospace(&item.icon);
+                }
+                ui.label(&item.name);
+                if ui.button("edit").clicked() {
+                    commands.spawn().insert(ItemEditor::new_for(&item.name));
+                }
+                if ui.button("rename").clicked() {
+                    commands.insert_resource(RenameDialog::new_for(item));
+                }
+                if ui.button("delete").clicked() {
+                    commands.insert_resource(DeleteItemDialog(item.name.clone()));
+                }
+            });
+        }
+    });
+}
+
+#[cfg(feature = "editor")]
+struct DeleteItemDialog(String);
+#[cfg(feature = "editor")]
+impl DeleteItemDialog {
+    fn render(
+        egui_context: ResMut<EguiContext>,
+        dialog: Option<ResMut<Self>>,
+        mut item_events: EventWriter<CrudEvent<Item>>,
+        mut commands: Commands,
+        slides: Query<&Slide>,
+        editors_open: Res<EditorsOpen>,
+    ) {
+        if dialog.is_none() {
+            return;
+        }
+        let dialog = dialog.unwrap();
+
+        let slides_with_references: Vec<_> = slides
+            .iter()
+            .filter(|s| slide_references_item(s, &dialog.0))
+            .map(|s| s.name.clone())
+            .collect();
+
+        if !editors_open.0 {
+            return;
+        }
+        egui::Window::new("Delete item").show(egui_context.ctx(), |ui| {
+            ui.horizontal(|ui| {
+                ui.label(format!("Going to delete \"{}\"", dialog.0));
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Cancel").clicked() {
+                    commands.remove_resource::<Self>();
+                }
+                if slides_with_references.is_empty() {
+                    if ui.button("Delete").clicked() {
+                        item_events.send(CrudEvent::Deleted(dialog.0.clone()));
+                        commands.remove_resource::<Self>();
+                    }
+                } else {
+                    ui.colored_label(egui::Color32::RED, "Can't delete, has references from:");
+                    for r in slides_with_references {
+                        ui.label(r);
+                    }
+                }
+            });
+        });
+    }
+}
+
+#[cfg(feature = "editor")]
+struct ItemEditor {
+    target: String,
+    ttl: usize,
+}
+
+#[cfg(feature = "editor")]
+impl ItemEditor {
+    fn new_for(target: &str) -> Self {
+        Self {
+            target: target.into(),
+            ttl: 3,
+        }
+    }
+    fn handle_renames(
+        mut editors: Query<&mut Self>,
+        mut item_events: EventReader<CrudEvent<Item>>,
+    ) {
+        for ev in item_events.iter() {
+            if let CrudEvent::Renamed(old_name, new_name) = ev {
+                for mut e in editors.iter_mut() {
+                    if e.target == *old_name {
+                        e.target = new_name.clone();
+                    }
+                }
+            }
+        }
+    }
+    fn render(
+        egui_context: ResMut<EguiContext>,
+        mut editors: Query<(Entity, &mut Self)>,
+        items: Query<&Item>,
+        mut item_events: EventWriter<CrudEvent<Item>>,
+        mut commands: Commands,
+        editors_open: Res<EditorsOpen>,
+    ) {
+        if !editors_open.0 {
+            return;
+        }
+        for (editor_id, mut editor) in editors.iter_mut() {
+            let saved = match items.iter().find(|i| i.name == editor.target) {
+                None => {
+                    // The item may have been created this very frame.
+                    if editor.ttl > 0 {
+                        editor.ttl -= 1;
+                    } else {
+                        commands.entity(editor_id).despawn();
+                    }
+                    continue;
+                }
+                Some(s) => s,
+            };
+            let mut unsaved = saved.clone();
+            let mut open = true;
+            egui::Window::new(format!("Edit: {}", saved.name))
+                .id(egui::Id::new(editor_id))
+                .open(&mut open)
+                .show(egui_context.ctx(), |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Name:");
+                        ui.label(&unsaved.name);
+                        if ui.small_button("rename").clicked() {
+                            commands.insert_resource(RenameDialog::new_for(&unsaved));
+                        }
+                    });
+                    ui.label("Description:");
+                    ui.text_edit_multiline(&mut unsaved.description);
+                    ui.label("Icon:");
+                    ui.add(
+                        egui::TextEdit::multiline(&mut unsaved.icon)
+                            .text_style(egui::TextStyle::Monospace),
+                    );
+                });
+            if !open {
+                commands.entity(editor_id).despawn();
+            }
+            if unsaved != *saved {
+                item_events.send(CrudEvent::Updated(unsaved.clone()));
+            }
+        }
+    }
+}
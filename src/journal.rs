@@ -0,0 +1,97 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+
+use crate::{
+    game_state::GameState,
+    model::Slide,
+    player::{Player, PlayerState},
+};
+
+const TOGGLE_KEY: KeyCode = KeyCode::J;
+const BACK_KEY: KeyCode = KeyCode::Back;
+
+/// Scrollable record of the path taken so far, toggled with J.
+pub struct JournalPlugin;
+
+impl Plugin for JournalPlugin {
+    fn build(&self, builder: &mut AppBuilder) {
+        builder
+            .add_system(Journal::handle_keys.system())
+            .add_system(Journal::render.system());
+    }
+}
+
+struct Journal;
+
+impl Journal {
+    fn handle_keys(
+        keys: Res<Input<KeyCode>>,
+        journal: Option<Res<Self>>,
+        egui_context: ResMut<EguiContext>,
+        mut player: ResMut<Player>,
+        mut player_state: ResMut<PlayerState>,
+        mut game_state: ResMut<GameState>,
+        mut commands: Commands,
+    ) {
+        if egui_context.ctx().wants_keyboard_input() {
+            return;
+        }
+        if keys.just_pressed(TOGGLE_KEY) {
+            if journal.is_some() {
+                commands.remove_resource::<Self>();
+            } else {
+                commands.insert_resource(Self);
+            }
+        }
+        if keys.just_pressed(BACK_KEY) {
+            player.step_back(&mut game_state, &mut player_state);
+        }
+    }
+
+    fn render(
+        egui_context: ResMut<EguiContext>,
+        journal: Option<Res<Self>>,
+        mut player: ResMut<Player>,
+        mut player_state: ResMut<PlayerState>,
+        mut game_state: ResMut<GameState>,
+        slides: Query<&Slide>,
+        mut commands: Commands,
+    ) {
+        if journal.is_none() {
+            return;
+        }
+        let mut open = true;
+        let mut back = false;
+        egui::Window::new("Journal")
+            .default_size([400., 500.])
+            .open(&mut open)
+            .show(egui_context.ctx(), |ui| {
+                ui.horizontal(|ui| {
+                    if player.can_step_back() && ui.button("Back one choice").clicked() {
+                        back = true;
+                    }
+                    ui.label("J: close, Backspace: back one choice");
+                });
+                ui.separator();
+                if player.history().is_empty() {
+                    ui.label("Nothing happened yet.");
+                }
+                egui::ScrollArea::auto_sized().show(ui, |ui| {
+                    for entry in player.history().iter() {
+                        match slides.iter().find(|s| s.id == entry.slide) {
+                            Some(slide) => ui.label(&slide.description),
+                            None => ui.colored_label(egui::Color32::GRAY, &entry.slide),
+                        };
+                        ui.colored_label(egui::Color32::LIGHT_BLUE, format!("> {}", entry.action));
+                        ui.separator();
+                    }
+                });
+            });
+        if back {
+            player.step_back(&mut game_state, &mut player_state);
+        }
+        if !open {
+            commands.remove_resource::<Self>();
+        }
+    }
+}
@@ -0,0 +1,32 @@
+//! The story core, plain data and the rules of playing it, and with the
+//! `editor` feature the Bevy editor and player built on top of it.
+
+pub mod ascii;
+pub mod export;
+pub mod game_state;
+pub mod graph_export;
+pub mod images;
+pub mod items;
+pub mod lint;
+pub mod manifest;
+pub mod model;
+pub mod persistence;
+pub mod project;
+pub mod saves;
+pub mod story;
+pub mod twee;
+
+#[cfg(feature = "editor")]
+pub mod editors;
+#[cfg(feature = "editor")]
+pub mod graph_view;
+#[cfg(feature = "editor")]
+pub mod journal;
+#[cfg(feature = "editor")]
+pub mod player;
+#[cfg(feature = "editor")]
+pub mod undo;
+#[cfg(feature = "editor")]
+pub mod unsaved;
+#[cfg(feature = "editor")]
+pub mod watch;
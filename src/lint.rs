@@ -0,0 +1,258 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
+};
+
+use crate::{
+    game_state::parse_condition,
+    images::Background,
+    manifest::StoryManifest,
+    model::{Crudable, Slide},
+    project::Project,
+    story::Story,
+};
+
+/// A slide a problem is about, found by its id and shown by its name, which
+/// need not be unique.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SlideRef {
+    pub id: String,
+    pub name: String,
+}
+
+impl SlideRef {
+    pub fn of(slide: &Slide) -> Self {
+        Self {
+            id: slide.id.clone(),
+            name: slide.name.clone(),
+        }
+    }
+}
+
+impl fmt::Display for SlideRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Problem {
+    MissingStartSlide { start: String },
+    DuplicateSlideName { slide: SlideRef },
+    BrokenLink { slide: SlideRef, action: String, target: String },
+    DeadEnd { slide: SlideRef },
+    Unreachable { slide: SlideRef },
+    EmptyActionText { slide: SlideRef, target: String },
+    DuplicateActionText { slide: SlideRef, text: String },
+    MissingBackground { slide: SlideRef, background: String },
+    InvalidCondition { slide: SlideRef, action: String, error: String },
+}
+
+impl Problem {
+    /// Id of the slide to open to fix the problem, if any.
+    pub fn slide(&self) -> Option<&str> {
+        match self {
+            Problem::MissingStartSlide { .. } => None,
+            Problem::DuplicateSlideName { slide }
+            | Problem::BrokenLink { slide, .. }
+            | Problem::DeadEnd { slide }
+            | Problem::Unreachable { slide }
+            | Problem::EmptyActionText { slide, .. }
+            | Problem::DuplicateActionText { slide, .. }
+            | Problem::MissingBackground { slide, .. }
+            | Problem::InvalidCondition { slide, .. } => Some(&slide.id),
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::MissingStartSlide { start } if start.is_empty() => {
+                write!(f, "The story has no start slide")
+            }
+            Problem::MissingStartSlide { start } => {
+                write!(f, "The start slide \"{}\" does not exist", start)
+            }
+            Problem::DuplicateSlideName { slide } => {
+                write!(f, "{}: another slide has the same name", slide)
+            }
+            Problem::BrokenLink {
+                slide,
+                action,
+                target,
+            } => write!(
+                f,
+                "{}: action \"{}\" goes to \"{}\", which does not exist",
+                slide, action, target
+            ),
+            Problem::DeadEnd { slide } => {
+                write!(f, "{}: has no actions and is not marked as an ending", slide)
+            }
+            Problem::Unreachable { slide } => {
+                write!(f, "{}: can not be reached from the start slide", slide)
+            }
+            Problem::EmptyActionText { slide, target } => {
+                write!(f, "{}: the action going to \"{}\" has no text", slide, target)
+            }
+            Problem::DuplicateActionText { slide, text } => {
+                write!(f, "{}: more than one action reads \"{}\"", slide, text)
+            }
+            Problem::MissingBackground { slide, background } => write!(
+                f,
+                "{}: background \"{}\" does not exist",
+                slide, background
+            ),
+            Problem::InvalidCondition {
+                slide,
+                action,
+                error,
+            } => write!(
+                f,
+                "{}: condition of action \"{}\" is invalid: {}",
+                slide, action, error
+            ),
+        }
+    }
+}
+
+/// Checks the story for mistakes the editor can not prevent on its own.
+pub fn lint(slides: &[Slide], backgrounds: &[Background], manifest: &StoryManifest) -> Vec<Problem> {
+    let mut problems = vec![];
+    let slide_ids: HashSet<&str> = slides.iter().map(|s| s.id.as_str()).collect();
+    let background_ids: HashSet<String> = backgrounds.iter().map(|b| b.id()).collect();
+    // Problems read by the author name slides, missing ones go by their id.
+    let slide_name = |id: &str| {
+        slides
+            .iter()
+            .find(|s| s.id == id)
+            .map_or(id.to_string(), |s| s.name.clone())
+    };
+
+    if !slide_ids.contains(manifest.start_slide.as_str()) {
+        problems.push(Problem::MissingStartSlide {
+            start: manifest.start_slide.clone(),
+        });
+    }
+
+    let mut name_counts: HashMap<&str, usize> = HashMap::new();
+    for s in slides.iter() {
+        *name_counts.entry(s.name.as_str()).or_default() += 1;
+    }
+
+    let mut sorted: Vec<&Slide> = slides.iter().collect();
+    sorted.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)));
+    for s in sorted.iter() {
+        // Names are what the author and Twee go by, links by name would be
+        // ambiguous.
+        if name_counts[s.name.as_str()] > 1 {
+            problems.push(Problem::DuplicateSlideName {
+                slide: SlideRef::of(s),
+            });
+        }
+        if !s.background.is_empty() && !background_ids.contains(&s.background) {
+            problems.push(Problem::MissingBackground {
+                slide: SlideRef::of(s),
+                background: s.background.clone(),
+            });
+        }
+        if s.actions.is_empty() && !s.ending {
+            problems.push(Problem::DeadEnd {
+                slide: SlideRef::of(s),
+            });
+        }
+        let mut seen_texts = HashSet::new();
+        for a in s.actions.iter() {
+            if !slide_ids.contains(a.target_slide.as_str()) {
+                problems.push(Problem::BrokenLink {
+                    slide: SlideRef::of(s),
+                    action: a.text.clone(),
+                    target: a.target_slide.clone(),
+                });
+            }
+            if a.text.trim().is_empty() {
+                problems.push(Problem::EmptyActionText {
+                    slide: SlideRef::of(s),
+                    target: slide_name(&a.target_slide),
+                });
+            } else if !seen_texts.insert(a.text.trim()) {
+                problems.push(Problem::DuplicateActionText {
+                    slide: SlideRef::of(s),
+                    text: a.text.clone(),
+                });
+            }
+            if !a.condition.trim().is_empty() {
+                if let Err(error) = parse_condition(&a.condition) {
+                    problems.push(Problem::InvalidCondition {
+                        slide: SlideRef::of(s),
+                        action: a.text.clone(),
+                        error,
+                    });
+                }
+            }
+        }
+    }
+
+    if slide_ids.contains(manifest.start_slide.as_str()) {
+        let reachable = reachable_from(slides, &manifest.start_slide);
+        for s in sorted.iter() {
+            if !reachable.contains(s.id.as_str()) && s.id != manifest.credits_slide {
+                problems.push(Problem::Unreachable {
+                    slide: SlideRef::of(s),
+                });
+            }
+        }
+    }
+
+    problems
+}
+
+/// Ids of the slides some sequence of actions leads to, conditions aside.
+pub fn reachable_from<'a>(slides: &'a [Slide], start: &str) -> HashSet<&'a str> {
+    let mut reachable = HashSet::new();
+    let mut queue: VecDeque<&str> = VecDeque::new();
+    if let Some(s) = slides.iter().find(|s| s.id == start) {
+        reachable.insert(s.id.as_str());
+        queue.push_back(s.id.as_str());
+    }
+    while let Some(id) = queue.pop_front() {
+        let slide = match slides.iter().find(|s| s.id == id) {
+            None => continue,
+            Some(s) => s,
+        };
+        for a in slide.actions.iter() {
+            if let Some(target) = slides.iter().find(|s| s.id == a.target_slide) {
+                if reachable.insert(target.id.as_str()) {
+                    queue.push_back(target.id.as_str());
+                }
+            }
+        }
+    }
+    reachable
+}
+
+/// Headless `--check` mode, lints the story files and returns the exit code.
+pub fn run_check(project: &Project) -> i32 {
+    let story = match Story::load(project) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return 2;
+        }
+    };
+    let problems = story.validate();
+    for p in problems.iter() {
+        println!("{}", p);
+    }
+    println!(
+        "Checked {} slides and {} backgrounds, found {} problems",
+        story.slides.len(),
+        story.backgrounds.len(),
+        problems.len()
+    );
+    if problems.is_empty() {
+        0
+    } else {
+        1
+    }
+}
@@ -8,32 +8,70 @@ use bevy::{
 use bevy::prelude::*;
 //use bevy::render::camera::OrthographicProjection;
 use bevy_egui::{egui, EguiContext, EguiPlugin, EguiSettings};
-use model::EditorsOpen;
+use fni2stib8::model::EditorsOpen;
 
-mod editors;
-mod images;
-mod model;
-mod persistence;
-mod player;
+use fni2stib8::{
+    editors, export, graph_export, images, items, journal, lint, manifest, model, persistence,
+    player, project, saves, twee, undo, unsaved, watch,
+};
 
-use crate::persistence::PersistenceEvent;
+use fni2stib8::{
+    ascii::placeholder_ascii,
+    images::{convert_background_to_ascii, Background, BackgroundData},
+    manifest::StoryManifest,
+    model::{CrudEvent, Crudable},
+    persistence::{BackupBrowser, PersistenceEvent, PersistenceResult},
+    project::{Project, ProjectDialog},
+    story::Story,
+    unsaved::{Autosave, UnsavedChanges},
+};
 
 pub fn main() {
+    let project = Project::from_args();
+    if std::env::args().any(|arg| arg == "--check") {
+        std::process::exit(lint::run_check(&project));
+    }
+    if std::env::args().any(|arg| arg == "--export-html") {
+        std::process::exit(export::run_export(&project));
+    }
+    if std::env::args().any(|arg| arg == "--dot") {
+        std::process::exit(graph_export::run_graph_export(
+            &project,
+            graph_export::GraphFormat::Dot,
+        ));
+    }
+    if std::env::args().any(|arg| arg == "--mermaid") {
+        std::process::exit(graph_export::run_graph_export(
+            &project,
+            graph_export::GraphFormat::Mermaid,
+        ));
+    }
+
     App::build()
         .insert_resource(LogSettings {
             level: Level::DEBUG,
             ..Default::default()
         })
         .insert_resource(EditorsOpen(false))
+        .insert_resource(project)
         .insert_resource(EguiSettings { scale_factor: 1.0 })
         .insert_resource(ClearColor(Color::rgb(0., 0., 0.)))
         .add_plugins(DefaultPlugins)
         .add_plugin(EguiPlugin)
+        .add_plugin(persistence::PersistenceReportPlugin)
+        .add_plugin(unsaved::UnsavedPlugin)
+        .add_plugin(watch::WatchPlugin)
+        .add_plugin(project::ProjectPlugin)
+        .add_plugin(undo::HistoryPlugin)
         .add_plugin(model::ModelPlugin)
+        .add_plugin(manifest::ManifestPlugin)
         .add_plugin(images::ImagesPlugin)
+        .add_plugin(items::ItemsPlugin)
         .add_plugin(persistence::PersistencePlugin::<model::Slide>::new())
         .add_plugin(editors::EditorsPlugin)
         .add_plugin(player::PlayerPlugin)
+        .add_plugin(saves::SavesPlugin)
+        .add_plugin(journal::JournalPlugin)
         .add_system(PersistConfirmationDialog::render.system())
         .add_startup_system(on_startup.system())
         .add_system(debug.system())
@@ -44,10 +82,14 @@ fn on_startup(
     mut commands: Commands,
     mut persistence: EventWriter<PersistenceEvent<model::Slide>>,
     mut persistence_bg: EventWriter<PersistenceEvent<images::Background>>,
+    mut persistence_items: EventWriter<PersistenceEvent<items::Item>>,
+    mut persistence_manifest: EventWriter<PersistenceEvent<StoryManifest>>,
 ) {
     info!("Started!");
+    persistence_manifest.send(PersistenceEvent::FileIn);
     persistence.send(PersistenceEvent::FileIn);
     persistence_bg.send(PersistenceEvent::FileIn);
+    persistence_items.send(PersistenceEvent::FileIn);
     commands.spawn_bundle(OrthographicCameraBundle::new_2d());
 }
 
@@ -57,21 +99,165 @@ fn debug(
     mut app_exit: EventWriter<AppExit>,
     mut buttons: EventReader<KeyboardInput>,
     mut editors_open: ResMut<EditorsOpen>,
+    mut manifest: ResMut<StoryManifest>,
+    project: Res<Project>,
+    slides: Query<&model::Slide>,
+    backgrounds: Query<(&Background, Option<&BackgroundData>)>,
+    items: Query<&items::Item>,
+    mut results: EventWriter<PersistenceResult>,
+    mut slide_events: EventWriter<CrudEvent<model::Slide>>,
+    unsaved: Res<UnsavedChanges>,
+    mut autosave: ResMut<Autosave>,
 ) {
     if editors_open.0 {
-        egui::Window::new("Main menu").show(egui_context.ctx(), |ui| {
+        let slide_name = |id: &str| {
+            slides
+                .iter()
+                .find(|s| s.id == id)
+                .map_or(id.to_string(), |s| s.name.clone())
+        };
+        let title = format!("Main menu{}", UnsavedChanges::marker(unsaved.any()));
+        // Keeps its place while the title changes.
+        egui::Window::new(title).id(egui::Id::new("main_menu")).show(egui_context.ctx(), |ui| {
+            ui.label(format!("Project: {}", project.name()));
+            ui.horizontal(|ui| {
+                if ui.button("Open").clicked() {
+                    commands.insert_resource(ProjectDialog::open(&project));
+                }
+                if ui.button("Save As").clicked() {
+                    commands.insert_resource(ProjectDialog::save_as(&project));
+                }
+            });
             if ui.button("File In").clicked() {
                 commands.insert_resource(PersistConfirmationDialog(PersistenceEvent::FileIn));
             }
             if ui.button("File Out").clicked() {
                 commands.insert_resource(PersistConfirmationDialog(PersistenceEvent::FileOut));
             }
+            ui.checkbox(
+                &mut autosave.enabled,
+                format!("Autosave every {} minutes", Autosave::interval_minutes()),
+            );
+            ui.horizontal(|ui| {
+                if ui.button("Twee In").clicked() {
+                    let story = current_story(&slides, &backgrounds, &items, &manifest);
+                    results.send(match twee::import(&project, &story) {
+                        Ok(imported) => {
+                            let count = imported.slides.len();
+                            // Merged through CRUD events so the import can be undone.
+                            for mut s in imported.slides.into_iter() {
+                                match slides.iter().find(|old| old.id == s.id) {
+                                    None => slide_events.send(CrudEvent::Created(s)),
+                                    Some(old) => {
+                                        s.position = s.position.or(old.position);
+                                        slide_events.send(CrudEvent::Updated(s));
+                                    }
+                                }
+                            }
+                            if let Some(title) = imported.title {
+                                manifest.title = title;
+                            }
+                            if let Some(start) = imported.start {
+                                manifest.start_slide = start;
+                            }
+                            PersistenceResult::Loaded {
+                                path: twee::file_path(&project),
+                                count,
+                            }
+                        }
+                        Err(e) => PersistenceResult::Failed(e),
+                    });
+                }
+                if ui.button("Twee Out").clicked() {
+                    let story = current_story(&slides, &backgrounds, &items, &manifest);
+                    results.send(match twee::export(&project, &story) {
+                        Ok(()) => PersistenceResult::Saved {
+                            path: twee::file_path(&project),
+                            count: story.slides.len(),
+                        },
+                        Err(e) => PersistenceResult::Failed(e),
+                    });
+                }
+            });
+            if ui.button("Restore backup").clicked() {
+                commands.insert_resource(BackupBrowser::new_for::<model::Slide>());
+            }
+            if ui.button("Export HTML").clicked() {
+                let story = current_story(&slides, &backgrounds, &items, &manifest);
+                // Images still loading or broken are exported as placeholders.
+                let ascii_backgrounds = backgrounds
+                    .iter()
+                    .map(|(bg, bgd)| {
+                        let ascii = match bgd {
+                            Some(bgd) => convert_background_to_ascii(bg, bgd, 1.0),
+                            None => placeholder_ascii(&format!("[ {} ]", bg.name())),
+                        };
+                        (bg.id(), ascii)
+                    })
+                    .collect();
+                let html = export::export_html(&story, &ascii_backgrounds);
+                results.send(match export::write_export(&project, &html) {
+                    Ok(path) => PersistenceResult::Saved {
+                        path,
+                        count: story.slides.len(),
+                    },
+                    Err(e) => PersistenceResult::Failed(e),
+                });
+            }
             if ui.button("Quit").clicked() {
-                app_exit.send(AppExit);
+                unsaved::quit(&unsaved, &mut commands, &mut app_exit);
             }
+            ui.separator();
+            ui.collapsing("Story", |ui| {
+                let mut edited = manifest.clone();
+                ui.horizontal(|ui| {
+                    ui.label("Title:");
+                    ui.text_edit_singleline(&mut edited.title);
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Author:");
+                    ui.text_edit_singleline(&mut edited.author);
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Version:");
+                    ui.text_edit_singleline(&mut edited.version);
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Start slide:");
+                    egui::ComboBox::from_id_source("start_slide")
+                        .selected_text(slide_name(&edited.start_slide))
+                        .show_ui(ui, |ui| {
+                            for s in slides.iter() {
+                                ui.selectable_value(&mut edited.start_slide, s.id.clone(), &s.name);
+                            }
+                        });
+                });
+                if !slides.iter().any(|s| s.id == edited.start_slide) {
+                    ui.colored_label(egui::Color32::RED, "The start slide does not exist");
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Credits slide:");
+                    egui::ComboBox::from_id_source("credits_slide")
+                        .selected_text(slide_name(&edited.credits_slide))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut edited.credits_slide, "".into(), "*None*");
+                            for s in slides.iter() {
+                                ui.selectable_value(&mut edited.credits_slide, s.id.clone(), &s.name);
+                            }
+                        });
+                });
+                if edited != *manifest {
+                    *manifest = edited;
+                }
+            });
         });
     }
 
+    if egui_context.ctx().wants_keyboard_input() {
+        // Typed into a text field, not shortcuts for later.
+        for _ in buttons.iter() {}
+        return;
+    }
     for ev in buttons.iter() {
         if ev.key_code == Some(KeyCode::E) && ev.state.is_pressed() {
             editors_open.0 = !editors_open.0;
@@ -79,6 +265,20 @@ fn debug(
     }
 }
 
+fn current_story(
+    slides: &Query<&model::Slide>,
+    backgrounds: &Query<(&Background, Option<&BackgroundData>)>,
+    items: &Query<&items::Item>,
+    manifest: &StoryManifest,
+) -> Story {
+    Story {
+        slides: slides.iter().cloned().collect(),
+        backgrounds: backgrounds.iter().map(|(bg, _)| bg.clone()).collect(),
+        items: items.iter().cloned().collect(),
+        manifest: manifest.clone(),
+    }
+}
+
 struct PersistConfirmationDialog(PersistenceEvent<()>);
 
 impl PersistConfirmationDialog {
@@ -88,6 +288,8 @@ impl PersistConfirmationDialog {
         dialog: Option<Res<Self>>,
         mut slide_persistence: EventWriter<PersistenceEvent<model::Slide>>,
         mut bg_persistence: EventWriter<PersistenceEvent<images::Background>>,
+        mut item_persistence: EventWriter<PersistenceEvent<items::Item>>,
+        mut manifest_persistence: EventWriter<PersistenceEvent<StoryManifest>>,
     ) {
         if dialog.is_none() {
             return;
@@ -98,22 +300,18 @@ impl PersistConfirmationDialog {
             ui.label(match dialog.0 {
                 PersistenceEvent::FileIn => "Doing a File In will erase all your unsaved changes.",
                 PersistenceEvent::FileOut => "Doing a File Out will override the file",
+                PersistenceEvent::Restore(_) => {
+                    "Restoring a backup will erase all your unsaved changes."
+                }
+                PersistenceEvent::Reload => "Reloading will merge the files into your changes.",
                 PersistenceEvent::_Phantom(_) => unreachable!(),
             });
             ui.horizontal(|ui| {
                 if ui.button("Proceed").clicked() {
-                    let bg_ev = match dialog.0 {
-                        PersistenceEvent::FileIn => PersistenceEvent::FileIn,
-                        PersistenceEvent::FileOut => PersistenceEvent::FileOut,
-                        PersistenceEvent::_Phantom(_) => unreachable!(),
-                    };
-                    bg_persistence.send(bg_ev);
-                    let slide_ev = match dialog.0 {
-                        PersistenceEvent::FileIn => PersistenceEvent::FileIn,
-                        PersistenceEvent::FileOut => PersistenceEvent::FileOut,
-                        PersistenceEvent::_Phantom(_) => unreachable!(),
-                    };
-                    slide_persistence.send(slide_ev);
+                    bg_persistence.send(dialog.0.cast());
+                    slide_persistence.send(dialog.0.cast());
+                    item_persistence.send(dialog.0.cast());
+                    manifest_persistence.send(dialog.0.cast());
                     commands.remove_resource::<Self>();
                 }
                 if ui.button("Cancel").clicked() {
@@ -0,0 +1,187 @@
+use serde::*;
+
+#[cfg(feature = "editor")]
+use bevy::prelude::*;
+
+use crate::{
+    persistence::{read_json, write_json, PersistenceError},
+    project::Project,
+};
+#[cfg(feature = "editor")]
+use crate::{
+    persistence::{write_sidecar, PersistenceEvent, PersistenceResult},
+    unsaved::{Autosave, UnsavedChanges},
+    watch::DiskTimes,
+};
+
+/// Story wide metadata, persisted on its own next to the slides.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct StoryManifest {
+    pub title: String,
+    #[serde(default)]
+    pub author: String,
+    #[serde(default)]
+    pub version: String,
+    /// Id of the slide the story starts at.
+    pub start_slide: String,
+    /// Id of the slide shown after the story, reachable or not.
+    #[serde(default)]
+    pub credits_slide: String,
+}
+
+impl Default for StoryManifest {
+    fn default() -> Self {
+        Self {
+            title: "Untitled story".into(),
+            author: String::new(),
+            version: "0.1.0".into(),
+            start_slide: String::new(),
+            credits_slide: String::new(),
+        }
+    }
+}
+
+impl StoryManifest {
+    pub fn file_name() -> &'static str {
+        "story.json"
+    }
+
+    /// Reads the manifest of the project, a missing file gives the default
+    /// one.
+    pub fn load(project: &Project) -> Result<Self, PersistenceError> {
+        let path = project.path(Self::file_name());
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        read_json(&path)
+    }
+
+    pub fn save(&self, project: &Project) -> Result<(), PersistenceError> {
+        write_json(&project.path(Self::file_name()), self)
+    }
+}
+
+#[cfg(feature = "editor")]
+pub struct ManifestPlugin;
+
+#[cfg(feature = "editor")]
+impl Plugin for ManifestPlugin {
+    fn build(&self, builder: &mut AppBuilder) {
+        builder
+            .insert_resource(StoryManifest::default())
+            .insert_resource(FiledManifest(StoryManifest::default()))
+            .add_event::<PersistenceEvent<StoryManifest>>()
+            .add_system(handler.system())
+            .add_system(track_changes.system())
+            .add_system(autosave.system().after("autosave"))
+            .add_system(update_window_title.system());
+    }
+}
+
+/// The manifest as of the last File In or File Out.
+#[cfg(feature = "editor")]
+struct FiledManifest(StoryManifest);
+
+#[cfg(feature = "editor")]
+fn handler(
+    mut events: EventReader<PersistenceEvent<StoryManifest>>,
+    mut manifest: ResMut<StoryManifest>,
+    project: Res<Project>,
+    mut results: EventWriter<PersistenceResult>,
+    mut filed: ResMut<FiledManifest>,
+    mut disk_times: ResMut<DiskTimes>,
+) {
+    let path = project.path(StoryManifest::file_name());
+    for e in events.iter() {
+        match e {
+            // Nothing refers to the manifest itself, so replacing it is as
+            // good as merging.
+            PersistenceEvent::FileIn | PersistenceEvent::Reload => {
+                if !path.exists() {
+                    warn!("Story manifest does not exist");
+                }
+                results.send(match StoryManifest::load(&project) {
+                    Ok(loaded) => {
+                        filed.0 = loaded.clone();
+                        disk_times.record(&path);
+                        *manifest = loaded;
+                        PersistenceResult::Loaded {
+                            path: path.clone(),
+                            count: 1,
+                        }
+                    }
+                    Err(e) => PersistenceResult::Failed(e),
+                });
+            }
+            PersistenceEvent::Restore(backup) => {
+                results.send(match read_json(backup) {
+                    Ok(loaded) => {
+                        *manifest = loaded;
+                        PersistenceResult::Loaded {
+                            path: path.clone(),
+                            count: 1,
+                        }
+                    }
+                    Err(e) => PersistenceResult::Failed(e),
+                });
+            }
+            PersistenceEvent::FileOut => {
+                results.send(match manifest.save(&project) {
+                    Ok(()) => {
+                        filed.0 = manifest.clone();
+                        disk_times.record(&path);
+                        PersistenceResult::Saved {
+                            path: path.clone(),
+                            count: 1,
+                        }
+                    }
+                    Err(e) => PersistenceResult::Failed(e),
+                });
+            }
+            PersistenceEvent::_Phantom(_) => unreachable!(),
+        }
+    }
+}
+
+#[cfg(feature = "editor")]
+fn track_changes(
+    manifest: Res<StoryManifest>,
+    filed: Res<FiledManifest>,
+    mut unsaved: ResMut<UnsavedChanges>,
+) {
+    if manifest.is_changed() || filed.is_changed() {
+        unsaved.set(StoryManifest::file_name(), *manifest != filed.0);
+    }
+}
+
+#[cfg(feature = "editor")]
+fn autosave(
+    autosave: Res<Autosave>,
+    unsaved: Res<UnsavedChanges>,
+    manifest: Res<StoryManifest>,
+    project: Res<Project>,
+    mut results: EventWriter<PersistenceResult>,
+) {
+    if !autosave.due() || !unsaved.contains(StoryManifest::file_name()) {
+        return;
+    }
+    let path = Autosave::sidecar_path(&project, StoryManifest::file_name());
+    match write_sidecar(&path, &*manifest) {
+        Ok(()) => info!("Autosaved the manifest to {}", path.display()),
+        Err(e) => results.send(PersistenceResult::Failed(e)),
+    }
+}
+
+#[cfg(feature = "editor")]
+fn update_window_title(
+    manifest: Res<StoryManifest>,
+    project: Res<Project>,
+    mut windows: ResMut<Windows>,
+) {
+    if !manifest.is_changed() && !project.is_changed() {
+        return;
+    }
+    if let Some(window) = windows.get_primary_mut() {
+        window.set_title(format!("{} - {}", manifest.title, project.name()));
+    }
+}
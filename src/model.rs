@@ -1,78 +1,149 @@
-use std::path::Path;
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
+#[cfg(feature = "editor")]
 use bevy::prelude::*;
 use serde::*;
 use serde_json;
 
-use crate::{images::Background, persistence::Persistable};
+use crate::{
+    game_state::{Effect, GameState},
+    persistence::{id_from_name, Persistable},
+};
+#[cfg(feature = "editor")]
+use crate::undo::UndoPlugin;
 
+#[cfg(feature = "editor")]
 pub struct ModelPlugin;
 
+#[cfg(feature = "editor")]
 pub struct EditorsOpen(pub bool);
 
+#[cfg(feature = "editor")]
 impl Plugin for ModelPlugin {
     fn build(&self, builder: &mut AppBuilder) {
         builder
             .add_plugin(CrudPlugin::<Slide>::new())
-            .add_system(update_references.system())
-            .add_system(update_references_to_backgrounds.system());
+            .add_plugin(UndoPlugin::<Slide>::new());
     }
 }
 
+/// A new id no other resource has, made of the time and a counter so ids
+/// created on the same millisecond still differ.
+pub fn new_id(prefix: &str) -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!("{}-{:x}-{:x}", prefix, millis, n)
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct Slide {
+    /// What actions and the manifest refer to the slide by, it never changes.
+    /// Files from before ids existed are migrated to have the name as id.
+    pub id: String,
+    /// Label shown to the author, free to change.
     pub name: String,
     pub description: String,
+    /// Id of the background, empty for none.
     #[serde(default)]
     pub background: String,
     pub actions: Vec<Action>,
+    /// Applied every time the player enters the slide.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub effects: Vec<Effect>,
+    /// Endings are allowed to have no actions.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ending: bool,
+    /// Where the slide sits in the editor's story graph.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<(i32, i32)>,
 }
 
 impl Persistable for Slide {
-    fn file_path() -> &'static Path {
-        Path::new("slides.json")
+    fn file_name() -> &'static str {
+        "slides.json"
     }
-    fn sortable_name<'a>(&'a self) -> &'a str {
+    fn sortable_name(&self) -> &str {
         &self.name
     }
+    fn migrate(from_version: u32, resource: &mut serde_json::Value) {
+        if from_version == 1 {
+            id_from_name(resource);
+        }
+    }
 }
 
 impl Slide {
     pub fn new(name: String) -> Self {
         Self {
+            id: new_id("slide"),
             name,
             background: "".into(),
             description: String::new(),
             actions: vec![],
+            effects: vec![],
+            ending: false,
+            position: None,
         }
     }
+
+    /// Actions whose condition holds for the given state, in display order.
+    pub fn available_actions<'a>(&'a self, state: &GameState) -> Vec<&'a Action> {
+        self.actions
+            .iter()
+            .filter(|a| state.check(&a.condition))
+            .collect()
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, Default)]
 pub struct Action {
     pub text: String,
+    /// Id of the slide the action leads to.
     pub target_slide: String,
+    /// Expression over the game state, the action is hidden when it is false.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub condition: String,
+    /// Applied when the player chooses the action.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub effects: Vec<Effect>,
 }
 
+#[cfg(feature = "editor")]
 pub struct CrudPlugin<R> {
     _phantom: std::marker::PhantomData<R>,
 }
 
+#[cfg(feature = "editor")]
 impl<R: 'static + Crudable> Plugin for CrudPlugin<R> {
     fn build(&self, builder: &mut AppBuilder) {
         builder
             .add_event::<CrudEvent<R>>()
-            .add_system(Self::event_handler.system());
+            .add_system(Self::event_handler.system().label("crud"));
     }
 }
 
 pub trait Crudable: Clone + Send + Sync + std::fmt::Debug {
+    /// What events and references identify the resource by. Resources
+    /// without an id of their own go by their name.
+    fn id(&self) -> String {
+        self.name()
+    }
     fn name(&self) -> String;
     fn set_name(&mut self, new_name: String);
     fn default_name_prefix() -> &'static str;
 }
 
 impl Crudable for Slide {
+    fn id(&self) -> String {
+        self.id.clone()
+    }
     fn name(&self) -> String {
         self.name.clone()
     }
@@ -84,44 +155,7 @@ impl Crudable for Slide {
     }
 }
 
-fn update_references_to_backgrounds(
-    mut events: EventReader<CrudEvent<Background>>,
-    mut query: Query<(Entity, &mut Slide)>,
-) {
-    for e in events.iter() {
-        match e {
-            CrudEvent::Renamed(old_name, new_name) => {
-                for (_, mut s) in query.iter_mut() {
-                    if s.background == *old_name {
-                        s.background = new_name.clone();
-                    }
-                }
-            }
-            _ => {}
-        }
-    }
-}
-
-fn update_references(
-    mut events: EventReader<CrudEvent<Slide>>,
-    mut query: Query<(Entity, &mut Slide)>,
-) {
-    for e in events.iter() {
-        match e {
-            CrudEvent::Renamed(old_name, new_name) => {
-                for (_, mut s) in query.iter_mut() {
-                    for mut a in s.actions.iter_mut() {
-                        if a.target_slide == *old_name {
-                            a.target_slide = new_name.clone();
-                        }
-                    }
-                }
-            }
-            _ => {}
-        }
-    }
-}
-
+#[cfg(feature = "editor")]
 impl<R: 'static + Crudable> CrudPlugin<R> {
     pub fn new() -> Self {
         Self {
@@ -141,21 +175,21 @@ impl<R: 'static + Crudable> CrudPlugin<R> {
                 }
                 CrudEvent::Updated(res) => {
                     for (_, mut s) in query.iter_mut() {
-                        if s.name() == res.name() {
+                        if s.id() == res.id() {
                             *s = res.clone();
                         }
                     }
                 }
-                CrudEvent::Renamed(old_name, new_name) => {
+                CrudEvent::Renamed(id, new_name) => {
                     for (_, mut s) in query.iter_mut() {
-                        if s.name() == *old_name {
+                        if s.id() == *id {
                             s.set_name(new_name.clone());
                         }
                     }
                 }
-                CrudEvent::Deleted(name) => {
+                CrudEvent::Deleted(id) => {
                     for (eid, s) in query.iter_mut() {
-                        if s.name() == *name {
+                        if s.id() == *id {
                             commands.entity(eid).despawn();
                         }
                     }
@@ -165,10 +199,12 @@ impl<R: 'static + Crudable> CrudPlugin<R> {
     }
 }
 
+/// Everything but `Created` addresses the resource by its id.
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum CrudEvent<R> {
     Created(R),
     Updated(R),
+    /// Id and new name.
     Renamed(String, String),
     Deleted(String),
 }
@@ -1,39 +1,504 @@
+#[cfg(feature = "editor")]
 use bevy::prelude::*;
-use serde::{de::DeserializeOwned, Serialize};
+#[cfg(feature = "editor")]
+use bevy_egui::{egui, EguiContext};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value as Json;
+use tracing::{info, warn};
+
+use crate::project::Project;
+#[cfg(feature = "editor")]
+use crate::{
+    model::{CrudEvent, Crudable},
+    story::Story,
+    undo::Replaying,
+    unsaved::{Autosave, UnsavedChanges},
+    watch::DiskTimes,
+};
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum PersistenceEvent<R> {
     FileOut,
     FileIn,
+    /// Replace the world with the contents of a backup of the file.
+    Restore(PathBuf),
+    /// Merge the file into the world through CRUD events, for changes made
+    /// to it outside the editor.
+    Reload,
     _Phantom((std::marker::PhantomData<R>, std::convert::Infallible)),
 }
 
+impl<R> PersistenceEvent<R> {
+    /// The same event addressed to another resource type.
+    pub fn cast<T>(&self) -> PersistenceEvent<T> {
+        match self {
+            PersistenceEvent::FileOut => PersistenceEvent::FileOut,
+            PersistenceEvent::FileIn => PersistenceEvent::FileIn,
+            PersistenceEvent::Restore(path) => PersistenceEvent::Restore(path.clone()),
+            PersistenceEvent::Reload => PersistenceEvent::Reload,
+            PersistenceEvent::_Phantom(_) => unreachable!(),
+        }
+    }
+}
+
+#[cfg(feature = "editor")]
 pub struct PersistencePlugin<R> {
     phantom: std::marker::PhantomData<R>,
 }
 
+#[cfg(feature = "editor")]
 impl<R> Plugin for PersistencePlugin<R>
 where
-    R: 'static + Persistable,
+    R: 'static + Persistable + Crudable,
 {
     fn build(&self, builder: &mut AppBuilder) {
+        builder
+            .world_mut()
+            .get_resource_or_insert_with(BackupKinds::default)
+            .0
+            .push(R::file_name());
         builder
             .add_event::<PersistenceEvent<R>>()
-            .add_system(Self::handler.system());
+            .insert_resource(Filed::<R>(vec![]))
+            .add_system(Self::handler.system())
+            .add_system(Self::backup_browser.system())
+            .add_system(Self::autosave.system().after("autosave"))
+            // Despawns are only seen once the commands of the update ran.
+            .add_system_to_stage(CoreStage::PostUpdate, Self::track_changes.system());
+    }
+}
+
+use std::{
+    fmt, fs,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// How many backups of each file File Out keeps around.
+const BACKUPS_KEPT: usize = 10;
+
+/// Version of the resource files this build writes.
+///
+/// 1. A bare array, before files had a version.
+/// 2. Slides and backgrounds have ids.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// What resource files look like on disk since version 2.
+#[derive(Serialize, Deserialize)]
+struct Envelope<T> {
+    version: u32,
+    resources: T,
+}
+
+pub trait Persistable: Clone + Send + Sync + PartialEq + Serialize + DeserializeOwned {
+    /// Name of the file within the project directory.
+    fn file_name() -> &'static str;
+    fn sortable_name(&self) -> &str;
+    /// Upgrades one resource as written by `from_version` to the version
+    /// after it. Called once for every version the file is behind.
+    fn migrate(_from_version: u32, _resource: &mut Json) {}
+}
+
+/// Version 2 migration of resources that gained an id: their name was what
+/// referred to them, so it becomes the id and references stay valid.
+pub fn id_from_name(resource: &mut Json) {
+    if let Some(object) = resource.as_object_mut() {
+        let missing = object.get("id").and_then(Json::as_str).map_or(true, str::is_empty);
+        if missing {
+            let name = object.get("name").cloned().unwrap_or_default();
+            object.insert("id".into(), name);
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PersistenceError {
+    pub path: PathBuf,
+    pub message: String,
+    /// Line and column of the offending JSON, when it failed to parse.
+    pub position: Option<(usize, usize)>,
+}
+
+impl PersistenceError {
+    pub(crate) fn io(path: &Path, e: std::io::Error) -> Self {
+        Self {
+            path: path.into(),
+            message: e.to_string(),
+            position: None,
+        }
+    }
+
+    fn json(path: &Path, e: serde_json::Error) -> Self {
+        Self {
+            path: path.into(),
+            message: e.to_string(),
+            // Errors found after parsing, like a missing field in a resource
+            // being migrated, have no position.
+            position: if e.is_io() || e.line() == 0 {
+                None
+            } else {
+                Some((e.line(), e.column()))
+            },
+        }
+    }
+}
+
+impl fmt::Display for PersistenceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.position {
+            Some((line, column)) => write!(
+                f,
+                "{}:{}:{}: {}",
+                self.path.display(),
+                line,
+                column,
+                self.message
+            ),
+            None => write!(f, "{}: {}", self.path.display(), self.message),
+        }
+    }
+}
+
+/// Outcome of every File In and File Out, for whoever wants to report it.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum PersistenceResult {
+    Loaded { path: PathBuf, count: usize },
+    /// The file was written by an older version and migrated while loading,
+    /// it stays in the old format until the next File Out.
+    Upgraded { path: PathBuf, from_version: u32 },
+    Saved { path: PathBuf, count: usize },
+    Failed(PersistenceError),
+}
+
+pub fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T, PersistenceError> {
+    let f = File::open(path).map_err(|e| PersistenceError::io(path, e))?;
+    serde_json::from_reader(std::io::BufReader::new(f)).map_err(|e| PersistenceError::json(path, e))
+}
+
+/// Writes next to the file first and renames over it, so a crash half way
+/// leaves the previous version intact. The previous version is backed up.
+pub fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), PersistenceError> {
+    let tmp_path = write_tmp(path, to_json(path, value)?.as_bytes())?;
+    if path.exists() {
+        backup(path)?;
+    }
+    fs::rename(&tmp_path, path).map_err(|e| PersistenceError::io(path, e))
+}
+
+/// Like `write_json` without the backup, for autosave sidecars and save slots.
+pub fn write_sidecar<T: Serialize>(path: &Path, value: &T) -> Result<(), PersistenceError> {
+    write_text(path, &to_json(path, value)?)
+}
+
+/// Replaces the file at once, without a backup, for files made from the
+/// story like exports.
+pub fn write_text(path: &Path, text: &str) -> Result<(), PersistenceError> {
+    write_bytes(path, text.as_bytes())
+}
+
+/// Like `write_text`, for files that are not text like cached images.
+pub fn write_bytes(path: &Path, bytes: &[u8]) -> Result<(), PersistenceError> {
+    let tmp_path = write_tmp(path, bytes)?;
+    fs::rename(&tmp_path, path).map_err(|e| PersistenceError::io(path, e))
+}
+
+/// Reads a file of app settings kept outside of any project, like the
+/// player settings. Missing or broken ones give the defaults.
+pub fn load_settings<T: DeserializeOwned + Default>(path: &str, what: &str) -> T {
+    match read_json(Path::new(path)) {
+        Ok(settings) => settings,
+        Err(e) => {
+            info!("Using default {}: {}", what, e);
+            T::default()
+        }
+    }
+}
+
+/// Writes a file of app settings, the app goes on without it on failure.
+pub fn save_settings<T: Serialize>(path: &str, settings: &T, what: &str) {
+    if let Err(e) = write_sidecar(Path::new(path), settings) {
+        warn!("Could not save the {}: {}", what, e);
+    }
+}
+
+fn to_json<T: Serialize>(path: &Path, value: &T) -> Result<String, PersistenceError> {
+    serde_json::to_string_pretty(value).map_err(|e| PersistenceError::json(path, e))
+}
+
+fn write_tmp(path: &Path, bytes: &[u8]) -> Result<PathBuf, PersistenceError> {
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let mut f = File::create(&tmp_path).map_err(|e| PersistenceError::io(&tmp_path, e))?;
+    f.write_all(bytes)
+        .and_then(|_| f.flush())
+        .and_then(|_| f.sync_all())
+        .map_err(|e| PersistenceError::io(&tmp_path, e))?;
+    Ok(tmp_path)
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Backup {
+    pub path: PathBuf,
+    pub taken_at: SystemTime,
+}
+
+/// Backups of the file are named `<file name>.<unix millis>.bak` and sit
+/// right next to it.
+pub fn list_backups(path: &Path) -> Vec<Backup> {
+    let file_name = match path.file_name().and_then(|n| n.to_str()) {
+        None => return vec![],
+        Some(n) => format!("{}.", n),
+    };
+    let dir = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    let mut backups: Vec<_> = match fs::read_dir(dir) {
+        Err(_) => return vec![],
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                let millis: u64 = name
+                    .strip_prefix(&file_name)?
+                    .strip_suffix(".bak")?
+                    .parse()
+                    .ok()?;
+                Some(Backup {
+                    path: entry.path(),
+                    taken_at: UNIX_EPOCH + Duration::from_millis(millis),
+                })
+            })
+            .collect(),
+    };
+    backups.sort_by_key(|b| std::cmp::Reverse(b.taken_at));
+    backups
+}
+
+fn backup(path: &Path) -> Result<(), PersistenceError> {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let mut backup_name = path.file_name().unwrap_or_default().to_os_string();
+    backup_name.push(format!(".{}.bak", millis));
+    let backup_path = path.with_file_name(backup_name);
+    fs::copy(path, &backup_path).map_err(|e| PersistenceError::io(&backup_path, e))?;
+    for old in list_backups(path).into_iter().skip(BACKUPS_KEPT) {
+        if let Err(e) = fs::remove_file(&old.path) {
+            warn!("Could not remove old backup {}: {}", old.path.display(), e);
+        }
+    }
+    Ok(())
+}
+
+/// Files that have a backup browser, in plugin order.
+#[cfg(feature = "editor")]
+#[derive(Default)]
+struct BackupKinds(Vec<&'static str>);
+
+/// The "Restore backup" window, showing the backups of one file at a time.
+#[cfg(feature = "editor")]
+pub struct BackupBrowser {
+    file: &'static str,
+}
+
+#[cfg(feature = "editor")]
+impl BackupBrowser {
+    pub fn new_for<R: Persistable>() -> Self {
+        Self {
+            file: R::file_name(),
+        }
+    }
+}
+
+#[cfg(feature = "editor")]
+fn describe_age(taken_at: SystemTime) -> String {
+    let secs = SystemTime::now()
+        .duration_since(taken_at)
+        .unwrap_or_default()
+        .as_secs();
+    match secs {
+        0..=59 => format!("{} seconds ago", secs),
+        60..=3599 => format!("{} minutes ago", secs / 60),
+        3600..=86399 => format!("{} hours ago", secs / 3600),
+        _ => format!("{} days ago", secs / 86400),
+    }
+}
+
+/// Reads resources from the file, or a backup of it, migrated up to
+/// `SCHEMA_VERSION`. Gives them along with the version the file had. Files
+/// from a newer version are refused, reading them could lose what this
+/// version does not know about on the next File Out.
+pub fn read_resources<R: Persistable>(path: &Path) -> Result<(Vec<R>, u32), PersistenceError> {
+    let (version, mut resources) = match read_json(path)? {
+        Json::Array(resources) => (1, resources),
+        file => {
+            let envelope: Envelope<Vec<Json>> =
+                serde_json::from_value(file).map_err(|e| PersistenceError::json(path, e))?;
+            (envelope.version, envelope.resources)
+        }
+    };
+    if version > SCHEMA_VERSION {
+        return Err(PersistenceError {
+            path: path.into(),
+            message: format!(
+                "written by a newer version of the editor (file version {}, this one reads up to {})",
+                version, SCHEMA_VERSION
+            ),
+            position: None,
+        });
+    }
+    for from_version in version..SCHEMA_VERSION {
+        for r in resources.iter_mut() {
+            R::migrate(from_version, r);
+        }
+    }
+    let resources = resources
+        .into_iter()
+        .map(serde_json::from_value)
+        .collect::<Result<Vec<R>, _>>()
+        .map_err(|e| PersistenceError::json(path, e))?;
+    Ok((resources, version))
+}
+
+fn sorted<R: Persistable>(mut resources: Vec<R>) -> Vec<R> {
+    resources.sort_by_key(|r| String::from(r.sortable_name()));
+    resources
+}
+
+/// Writes every resource of the type to its file in the project sorted by
+/// name, so diffs of the file stay small. Gives how many were written. The
+/// story saves through it, see `Story::save_part`.
+pub(crate) fn save_resources<R: Persistable>(
+    project: &Project,
+    resources: Vec<R>,
+) -> Result<usize, PersistenceError> {
+    let resources = sorted(resources);
+    let envelope = Envelope {
+        version: SCHEMA_VERSION,
+        resources: &resources,
+    };
+    write_json(&project.path(R::file_name()), &envelope)?;
+    Ok(resources.len())
+}
+
+/// Registers the `PersistenceResult` events and shows the failures.
+#[cfg(feature = "editor")]
+pub struct PersistenceReportPlugin;
+
+#[cfg(feature = "editor")]
+impl Plugin for PersistenceReportPlugin {
+    fn build(&self, builder: &mut AppBuilder) {
+        builder
+            .add_event::<PersistenceResult>()
+            .insert_resource(PersistenceErrors::default())
+            .insert_resource(FileUpgrades::default())
+            .add_system(PersistenceErrors::collect.system())
+            .add_system(PersistenceErrors::render.system())
+            .add_system(FileUpgrades::collect.system())
+            .add_system(FileUpgrades::render.system());
     }
 }
 
-use std::fs::File;
-use std::path::Path;
+#[cfg(feature = "editor")]
+#[derive(Debug, Default)]
+struct PersistenceErrors(Vec<PersistenceError>);
+
+#[cfg(feature = "editor")]
+impl PersistenceErrors {
+    fn collect(mut results: EventReader<PersistenceResult>, mut errors: ResMut<Self>) {
+        for r in results.iter() {
+            match r {
+                PersistenceResult::Failed(e) => {
+                    error!("{}", e);
+                    errors.0.push(e.clone());
+                }
+                PersistenceResult::Loaded { path, count } => {
+                    info!("Loaded {} resources from {}", count, path.display())
+                }
+                PersistenceResult::Upgraded { .. } => {}
+                PersistenceResult::Saved { path, count } => {
+                    info!("Wrote {} resources to {}", count, path.display())
+                }
+            }
+        }
+    }
 
-pub trait Persistable: Clone + Send + Sync + Serialize + DeserializeOwned {
-    fn file_path() -> &'static Path;
-    fn sortable_name<'a>(&'a self) -> &'a str;
+    fn render(egui_context: ResMut<EguiContext>, mut errors: ResMut<Self>) {
+        if errors.0.is_empty() {
+            return;
+        }
+        egui::Window::new("File errors").show(egui_context.ctx(), |ui| {
+            for e in errors.0.iter() {
+                ui.colored_label(egui::Color32::RED, e.to_string());
+            }
+            ui.label("Nothing was changed for the files that failed.");
+            if ui.button("Dismiss").clicked() {
+                errors.0.clear();
+            }
+        });
+    }
+}
+
+/// Files migrated from an older version on load, with the version they had.
+#[cfg(feature = "editor")]
+#[derive(Debug, Default)]
+struct FileUpgrades(Vec<(PathBuf, u32)>);
+
+#[cfg(feature = "editor")]
+impl FileUpgrades {
+    fn collect(mut results: EventReader<PersistenceResult>, mut upgrades: ResMut<Self>) {
+        for r in results.iter() {
+            if let PersistenceResult::Upgraded { path, from_version } = r {
+                warn!(
+                    "{} was written by version {} and upgraded to version {}",
+                    path.display(),
+                    from_version,
+                    SCHEMA_VERSION
+                );
+                upgrades.0.push((path.clone(), *from_version));
+            }
+        }
+    }
+
+    fn render(egui_context: ResMut<EguiContext>, mut upgrades: ResMut<Self>) {
+        if upgrades.0.is_empty() {
+            return;
+        }
+        egui::Window::new("Upgraded files").show(egui_context.ctx(), |ui| {
+            for (path, from_version) in upgrades.0.iter() {
+                ui.colored_label(
+                    egui::Color32::YELLOW,
+                    format!(
+                        "{} is from version {}, upgraded to {}",
+                        path.display(),
+                        from_version,
+                        SCHEMA_VERSION
+                    ),
+                );
+            }
+            ui.label("File Out writes them in the new format, older versions can not read that.");
+            if ui.button("Dismiss").clicked() {
+                upgrades.0.clear();
+            }
+        });
+    }
 }
 
+/// What the file had on the last File In or File Out, sorted like the file,
+/// to tell whether the world has unsaved changes.
+#[cfg(feature = "editor")]
+struct Filed<R>(Vec<R>);
+
+#[cfg(feature = "editor")]
 impl<R> PersistencePlugin<R>
 where
-    R: 'static + Persistable,
+    R: 'static + Persistable + Crudable,
 {
     pub fn new() -> Self {
         Self {
@@ -44,39 +509,337 @@ where
     fn handler(
         mut events: EventReader<PersistenceEvent<R>>,
         resources: Query<(Entity, &R)>,
+        project: Res<Project>,
+        mut results: EventWriter<PersistenceResult>,
+        mut filed: ResMut<Filed<R>>,
+        mut disk_times: ResMut<DiskTimes>,
+        mut crud_events: EventWriter<CrudEvent<R>>,
+        mut replaying: Option<ResMut<Replaying<R>>>,
         mut commands: Commands,
     ) {
+        let path = project.path(R::file_name());
         for e in events.iter() {
-            match e {
-                PersistenceEvent::FileIn => {
-                    for (se, _res) in resources.iter() {
-                        commands.entity(se).despawn();
-                    }
-
-                    if R::file_path().exists() {
-                        info!("File exists, loading!");
-                        let f = File::open(R::file_path()).expect("Failed to read resources file");
-                        let resources: Vec<R> =
-                            serde_json::from_reader(f).expect("Failed to parse resources file");
-                        for res in resources.iter() {
-                            commands.spawn().insert(res.clone());
-                        }
-                        info!("Loaded {} resources", resources.len());
-                    } else {
+            // A restored backup is not what the file has, so it stays unsaved.
+            let from_file = matches!(e, PersistenceEvent::FileIn | PersistenceEvent::Reload);
+            let loaded: Result<(Vec<R>, u32), _> = match e {
+                PersistenceEvent::FileIn | PersistenceEvent::Reload => {
+                    if !path.exists() {
                         warn!("File does not exist");
                     }
+                    Story::load_part(&project)
                 }
+                PersistenceEvent::Restore(path) => read_resources(path),
                 PersistenceEvent::FileOut => {
                     info!("Writing to file!");
-                    let mut resources: Vec<R> = resources.iter().map(|(_, res)| res).cloned().collect();
-                    resources.sort_by_key(|r|String::from(r.sortable_name()));
-                    let f = File::create(R::file_path()).expect("Failed to write to resources file");
-                    serde_json::to_writer_pretty(f, &resources)
-                        .expect("Failed to rialize to resources files");
-                    info!("Wrote {} resources", resources.len());
+                    let resources = sorted(resources.iter().map(|(_, res)| res).cloned().collect());
+                    results.send(match Story::save_part(&project, resources.clone()) {
+                        Ok(count) => {
+                            filed.0 = resources;
+                            disk_times.record(&path);
+                            PersistenceResult::Saved {
+                                path: path.clone(),
+                                count,
+                            }
+                        }
+                        Err(e) => PersistenceResult::Failed(e),
+                    });
+                    continue;
                 }
                 PersistenceEvent::_Phantom(_) => unreachable!(),
+            };
+            // Only touch the world once the whole file is known to be good.
+            match loaded {
+                Err(e) => results.send(PersistenceResult::Failed(e)),
+                Ok((loaded, version)) => {
+                    if matches!(e, PersistenceEvent::Reload) {
+                        // Entities that stay keep their editors, and the
+                        // player its slide.
+                        let current: Vec<&R> = resources.iter().map(|(_, res)| res).collect();
+                        let mut merge = vec![];
+                        for res in loaded.iter() {
+                            match current.iter().find(|c| c.id() == res.id()) {
+                                None => merge.push(CrudEvent::Created(res.clone())),
+                                Some(c) if *c != res => merge.push(CrudEvent::Updated(res.clone())),
+                                Some(_) => {}
+                            }
+                        }
+                        for c in current.iter() {
+                            if !loaded.iter().any(|res| res.id() == c.id()) {
+                                merge.push(CrudEvent::Deleted(c.id()));
+                            }
+                        }
+                        // What is on disk is not an edit to undo.
+                        for m in merge {
+                            if let Some(replaying) = replaying.as_mut() {
+                                replaying.skip(&m);
+                            }
+                            crud_events.send(m);
+                        }
+                    } else {
+                        for (se, _res) in resources.iter() {
+                            commands.entity(se).despawn();
+                        }
+                        for res in loaded.iter() {
+                            commands.spawn().insert(res.clone());
+                        }
+                    }
+                    if from_file {
+                        filed.0 = sorted(loaded.clone());
+                        disk_times.record(&path);
+                    }
+                    results.send(PersistenceResult::Loaded {
+                        path: path.clone(),
+                        count: loaded.len(),
+                    });
+                    if version < SCHEMA_VERSION {
+                        results.send(PersistenceResult::Upgraded {
+                            path: path.clone(),
+                            from_version: version,
+                        });
+                    }
+                }
             }
         }
     }
+
+    fn track_changes(
+        changed: Query<(), Changed<R>>,
+        removed: RemovedComponents<R>,
+        resources: Query<&R>,
+        filed: Res<Filed<R>>,
+        mut unsaved: ResMut<UnsavedChanges>,
+    ) {
+        if changed.iter().next().is_none() && removed.iter().next().is_none() && !filed.is_changed() {
+            return;
+        }
+        let current = sorted(resources.iter().cloned().collect());
+        unsaved.set(R::file_name(), current != filed.0);
+    }
+
+    fn autosave(
+        autosave: Res<Autosave>,
+        unsaved: Res<UnsavedChanges>,
+        resources: Query<&R>,
+        project: Res<Project>,
+        mut results: EventWriter<PersistenceResult>,
+    ) {
+        if !autosave.due() || !unsaved.contains(R::file_name()) {
+            return;
+        }
+        let resources = sorted(resources.iter().cloned().collect());
+        let envelope = Envelope {
+            version: SCHEMA_VERSION,
+            resources: &resources,
+        };
+        let path = Autosave::sidecar_path(&project, R::file_name());
+        match write_sidecar(&path, &envelope) {
+            Ok(()) => info!("Autosaved {} resources to {}", resources.len(), path.display()),
+            Err(e) => results.send(PersistenceResult::Failed(e)),
+        }
+    }
+
+    fn backup_browser(
+        egui_context: ResMut<EguiContext>,
+        browser: Option<ResMut<BackupBrowser>>,
+        kinds: Res<BackupKinds>,
+        project: Res<Project>,
+        current: Query<&R>,
+        mut preview: Local<Option<(PathBuf, Result<(Vec<R>, u32), PersistenceError>)>>,
+        // Read when the browser opens on this file, not on every frame.
+        mut backups: Local<Option<Vec<Backup>>>,
+        mut persistence: EventWriter<PersistenceEvent<R>>,
+        mut commands: Commands,
+    ) {
+        let mut browser = match browser {
+            Some(b) if b.file == R::file_name() => b,
+            _ => {
+                *preview = None;
+                *backups = None;
+                return;
+            }
+        };
+        egui::Window::new("Restore backup").show(egui_context.ctx(), |ui| {
+            ui.horizontal(|ui| {
+                for k in kinds.0.iter() {
+                    if ui
+                        .selectable_label(browser.file == *k, *k)
+                        .clicked()
+                    {
+                        browser.file = *k;
+                    }
+                }
+            });
+            ui.separator();
+            let backups =
+                backups.get_or_insert_with(|| list_backups(&project.path(R::file_name())));
+            if backups.is_empty() {
+                ui.label("No backups yet, one is made on every File Out.");
+            }
+            for b in backups.iter() {
+                let selected = preview.as_ref().map_or(false, |(p, _)| *p == b.path);
+                if ui
+                    .selectable_label(selected, describe_age(b.taken_at))
+                    .clicked()
+                {
+                    *preview = Some((b.path.clone(), read_resources(&b.path)));
+                }
+            }
+            ui.separator();
+            match &*preview {
+                None => {}
+                Some((_, Err(e))) => {
+                    ui.colored_label(egui::Color32::RED, e.to_string());
+                }
+                Some((path, Ok((backup, version)))) => {
+                    let current: Vec<&R> = current.iter().collect();
+                    ui.label(format!(
+                        "{} entries in the backup, {} now",
+                        backup.len(),
+                        current.len()
+                    ));
+                    for r in backup.iter() {
+                        match current.iter().find(|c| c.id() == r.id()) {
+                            None => {
+                                ui.colored_label(
+                                    egui::Color32::GREEN,
+                                    format!("+ {}", r.sortable_name()),
+                                );
+                            }
+                            Some(c) if *c != r => {
+                                ui.colored_label(
+                                    egui::Color32::YELLOW,
+                                    format!("~ {}", r.sortable_name()),
+                                );
+                            }
+                            Some(_) => {}
+                        }
+                    }
+                    for c in current.iter() {
+                        if !backup.iter().any(|r| r.id() == c.id()) {
+                            ui.colored_label(
+                                egui::Color32::RED,
+                                format!("- {}", c.sortable_name()),
+                            );
+                        }
+                    }
+                    if *version < SCHEMA_VERSION {
+                        ui.label(format!("The backup is from version {}, it will be upgraded.", version));
+                    }
+                    ui.label("Restoring replaces what is loaded now, File Out to keep it.");
+                    if ui.button("Restore").clicked() {
+                        persistence.send(PersistenceEvent::Restore(path.clone()));
+                        commands.remove_resource::<BackupBrowser>();
+                    }
+                }
+            }
+            if ui.button("Close").clicked() {
+                commands.remove_resource::<BackupBrowser>();
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{model::Slide, story::Story};
+    use std::{thread, time::Duration};
+
+    fn files_in(project: &Project) -> Vec<String> {
+        let mut names: Vec<_> = fs::read_dir(&project.dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn write_text_replaces_the_file_without_leftovers() {
+        let project = Project::temp("write_text");
+        let path = project.path("story.html");
+        write_text(&path, "first").unwrap();
+        write_text(&path, "second").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+        assert_eq!(files_in(&project), vec!["story.html".to_string()]);
+        fs::remove_dir_all(&project.dir).unwrap();
+    }
+
+    #[test]
+    fn write_json_backs_up_the_previous_version() {
+        let project = Project::temp("write_json");
+        let path = project.path("slides.json");
+        write_json(&path, &vec!["first"]).unwrap();
+        assert!(list_backups(&path).is_empty());
+        write_json(&path, &vec!["second"]).unwrap();
+        assert_eq!(read_json::<Vec<String>>(&path).unwrap(), vec!["second"]);
+        let backups = list_backups(&path);
+        assert_eq!(backups.len(), 1);
+        assert_eq!(read_json::<Vec<String>>(&backups[0].path).unwrap(), vec!["first"]);
+        assert!(!files_in(&project).iter().any(|name| name.ends_with(".tmp")));
+        fs::remove_dir_all(&project.dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_only_the_latest_backups() {
+        let project = Project::temp("rotation");
+        let path = project.path("slides.json");
+        for i in 0..BACKUPS_KEPT + 3 {
+            write_json(&path, &vec![i]).unwrap();
+            // Backups are named by the millisecond they were taken at.
+            thread::sleep(Duration::from_millis(2));
+        }
+        let backups = list_backups(&path);
+        assert_eq!(backups.len(), BACKUPS_KEPT);
+        let kept: Vec<Vec<usize>> = backups.iter().map(|b| read_json(&b.path).unwrap()).collect();
+        let expected: Vec<Vec<usize>> = (2..BACKUPS_KEPT + 2).rev().map(|i| vec![i]).collect();
+        assert_eq!(kept, expected);
+        fs::remove_dir_all(&project.dir).unwrap();
+    }
+
+    #[test]
+    fn gives_resources_their_name_as_id() {
+        let mut named = serde_json::json!({ "name": "hall" });
+        id_from_name(&mut named);
+        assert_eq!(named, serde_json::json!({ "id": "hall", "name": "hall" }));
+        let mut empty_id = serde_json::json!({ "id": "", "name": "hall" });
+        id_from_name(&mut empty_id);
+        assert_eq!(empty_id["id"], "hall");
+        let mut with_id = serde_json::json!({ "id": "slide-1", "name": "hall" });
+        id_from_name(&mut with_id);
+        assert_eq!(with_id["id"], "slide-1");
+    }
+
+    #[test]
+    fn migrates_version_1_files() {
+        let project = Project::temp("migration");
+        let path = project.path(Slide::file_name());
+        fs::write(
+            &path,
+            r#"[{ "name": "hall", "description": "", "actions": [{ "text": "Go", "target_slide": "yard" }] }]"#,
+        )
+        .unwrap();
+        let (slides, version) = read_resources::<Slide>(&path).unwrap();
+        assert_eq!(version, 1);
+        assert_eq!(slides.len(), 1);
+        assert_eq!(slides[0].id, "hall");
+        assert_eq!(slides[0].actions[0].target_slide, "yard");
+
+        // Written back in the current version, which reads as it is.
+        Story::save_part(&project, slides.clone()).unwrap();
+        assert_eq!(read_resources::<Slide>(&path), Ok((slides, SCHEMA_VERSION)));
+        fs::remove_dir_all(&project.dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_files_from_newer_versions() {
+        let project = Project::temp("newer_version");
+        let path = project.path(Slide::file_name());
+        let newer = format!(r#"{{ "version": {}, "resources": [] }}"#, SCHEMA_VERSION + 1);
+        fs::write(&path, newer).unwrap();
+        let e = read_resources::<Slide>(&path).unwrap_err();
+        assert!(e.message.contains("newer version"));
+        assert_eq!(e.position, None);
+        fs::remove_dir_all(&project.dir).unwrap();
+    }
 }
@@ -1,37 +1,52 @@
 use crate::{
-    images::{convert_background_to_ascii, Background, BackgroundData},
+    ascii::{placeholder_ascii, ColoredAscii},
+    game_state::GameState,
+    images::{
+        convert_background_to_ascii, convert_background_to_colored_ascii, Background,
+        BackgroundData, LoadStatus,
+    },
+    items::Item,
+    manifest::StoryManifest,
     model::*,
+    persistence::{load_settings, save_settings},
+    saves::{HistoryEntry, SaveGame},
+    story::Progress,
 };
 use bevy::{input::{ElementState, mouse::MouseButtonInput}, prelude::*, render::camera::Camera};
 use bevy_egui::{
     egui::{self, ScrollArea, TextEdit},
     EguiContext,
 };
+use serde::*;
 
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, builder: &mut AppBuilder) {
+        let player = Player::new();
+        let first_fade = PlayerState::FadeInBg(player.phase(BG_FADE_IN * 3.0));
         builder
-            .insert_resource(Player::new())
-            .insert_resource(PlayerState::FadeInBg(Timer::from_seconds(
-                BG_FADE_IN*3.0, false,
-            )))
+            .insert_resource(player)
+            .insert_resource(GameState::default())
+            .insert_resource(first_fade)
             .add_startup_system(Player::startup.system())
+            .add_system(Player::start_from_manifest.system())
             .add_system(Player::render.system())
+            .add_system(Player::render_controls.system())
             .add_system(Player::handle_mouse.system())
-            .add_system(Player::update_state.system())
-            .add_system(Player::handle_renames.system());
+            .add_system(Player::handle_keys.system())
+            .add_system(Player::update_state.system());
     }
 }
 
 struct DisplayBackground;
 struct DisplayDescription;
 struct DisplayActions;
+struct DisplayInventory;
 
 #[derive(Debug)]
-struct Player {
-    current_slide: String,
+pub(crate) struct Player {
+    progress: Progress,
     next_slide: String,
     bg_opacity: f32,
     percentage_of_text_shown: f32,
@@ -41,12 +56,94 @@ struct Player {
     dashes: usize,
     render: bool,
     redraw_bg: bool,
+    /// The coloured background the text holds, converted at full opacity.
+    bg_key: Option<BackgroundKey>,
     pauses: f32,
     action_pause: f32,
+    settings: PlayerSettings,
+}
+
+/// Reader preferences, kept apart from the save games.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct PlayerSettings {
+    /// Divides the length of every phase, 2.0 plays everything twice as fast.
+    text_speed: f32,
+    /// Slides already left once before show up at once when revisited.
+    skip_seen: bool,
+    /// Backgrounds keep the colours of their images instead of being white.
+    #[serde(default)]
+    colored_backgrounds: bool,
+}
+
+impl Default for PlayerSettings {
+    fn default() -> Self {
+        Self {
+            text_speed: 1.0,
+            skip_seen: false,
+            colored_backgrounds: false,
+        }
+    }
+}
+
+const WHITE: [u8; 3] = [255, 255, 255];
+
+/// The background and the image it had loaded when it was last converted.
+type BackgroundKey = (Background, egui::TextureId);
+
+/// One section for every run of a colour, in the style of the first one.
+fn set_background_sections(text: &mut Text, ascii: &ColoredAscii) {
+    if ascii.0.is_empty() {
+        return;
+    }
+    let style = text.sections.first().unwrap().style.clone();
+    text.sections = ascii
+        .0
+        .iter()
+        .map(|(value, [r, g, b])| TextSection {
+            value: value.clone(),
+            style: TextStyle {
+                color: Color::rgb_u8(*r, *g, *b),
+                ..style.clone()
+            },
+        })
+        .collect();
+}
+
+/// All in white, like the background was before colours. It fades by being
+/// converted again with dimmer glyphs.
+fn set_background_text(text: &mut Text, value: &str) {
+    let plain = text.sections.len() == 1 && text.sections[0].style.color == Color::WHITE;
+    if plain && text.sections[0].value == value {
+        return;
+    }
+    set_background_sections(text, &ColoredAscii::uniform(value.into(), WHITE));
+}
+
+/// Coloured backgrounds fade through the alpha of their sections, the characters
+/// stay as they were converted.
+fn set_background_opacity(text: &mut Text, opacity: f32) {
+    if text.sections.first().map_or(true, |s| s.style.color.a() == opacity) {
+        return;
+    }
+    for s in text.sections.iter_mut() {
+        s.style.color.set_a(opacity);
+    }
+}
+
+const SETTINGS_PATH: &str = "player_settings.json";
+
+impl PlayerSettings {
+    fn load() -> Self {
+        load_settings(SETTINGS_PATH, "player settings")
+    }
+
+    fn save(&self) {
+        save_settings(SETTINGS_PATH, self, "player settings");
+    }
 }
 
 #[derive(Debug, Clone)]
-enum PlayerState {
+pub(crate) enum PlayerState {
     FadeInBg(Timer),
     PauseBetweenBgAndText(Timer),
     FadeInText(Timer),
@@ -58,21 +155,52 @@ enum PlayerState {
     FadeOutBg(Timer),
 }
 
+impl PlayerState {
+    fn phase_timer(&mut self) -> Option<&mut Timer> {
+        use PlayerState::*;
+        match self {
+            FadeInBg(t)
+            | PauseBetweenBgAndText(t)
+            | FadeInText(t)
+            | PauseBetweenTextAndActions(t)
+            | FadeInActions(t)
+            | FadeOutTextAndActions(t)
+            | FadeOutBg(t) => Some(t),
+            WaitingForInput(_) | GotInput => None,
+        }
+    }
+
+    /// Makes the current phase end on the next update, as if its time ran out.
+    fn finish_phase(&mut self) {
+        if let Some(timer) = self.phase_timer() {
+            let duration = timer.duration();
+            timer.set_elapsed(duration);
+        }
+    }
+}
+
 impl Player {
     fn update_state(
         mut player: ResMut<Self>,
         mut state: ResMut<PlayerState>,
+        mut game_state: ResMut<GameState>,
         time: Res<Time>,
         slides: Query<&Slide>,
     ) {
         let slide = slides
             .iter()
-            .find(|slide| slide.name == player.current_slide);
+            .find(|slide| slide.id == player.progress.current_slide);
 
         if slide.is_none() {
             return;
         }
         let slide = slide.unwrap();
+        if player.progress.enter(slide, &mut game_state)
+            && player.settings.skip_seen
+            && player.progress.has_seen(&slide.id)
+        {
+            player.skip(&mut state);
+        }
         let text_fade_in_duration =
             (slide.description.len() as f32 / MEAN_WORD_LENGTH) / MEAN_READING_SPEED_WPS;
 
@@ -83,14 +211,14 @@ impl Player {
                 if timer.tick(time.delta()).just_finished() {
                     info!("FadeInBg finished");
                     player.bg_opacity = 1.0;
-                    *state = PauseBetweenBgAndText(Timer::from_seconds(player.pauses, false))
+                    *state = PauseBetweenBgAndText(player.phase(player.pauses))
                 }
             }
             PauseBetweenBgAndText(ref mut timer) => {
                 player.redraw_bg = false;
                 if timer.tick(time.delta()).just_finished() {
                     info!("PauseBetweenBgAndText finished");
-                    *state = FadeInText(Timer::from_seconds(text_fade_in_duration, false))
+                    *state = FadeInText(player.phase(text_fade_in_duration))
                 }
             }
             FadeInText(ref mut timer) => {
@@ -98,15 +226,14 @@ impl Player {
                 if timer.tick(time.delta()).just_finished() {
                     info!("FadeInText finished");
                     player.percentage_of_text_shown = 1.0;
-                    *state = PauseBetweenTextAndActions(Timer::from_seconds(player.pauses, false))
+                    *state = PauseBetweenTextAndActions(player.phase(player.pauses))
                 }
             }
             PauseBetweenTextAndActions(ref mut timer) => {
                 if timer.tick(time.delta()).just_finished() {
                     info!("PauseBetweenTextAndActions finished");
-                    *state = FadeInActions(Timer::from_seconds(
-                        slide.actions.len() as f32 * player.action_pause,
-                        false,
+                    *state = FadeInActions(player.phase(
+                        slide.available_actions(&game_state).len() as f32 * player.action_pause,
                     ))
                 }
             }
@@ -120,9 +247,13 @@ impl Player {
             }
             WaitingForInput(ref mut timer) => {
                 player.dashes = (timer.percent() * 4.0) as usize;
-                if timer.tick(time.delta()).just_finished() {}
+                if timer.tick(time.delta()).just_finished() {
+                    // A skipped fade in leaves the background to be redrawn
+                    // at full opacity, by now it surely was.
+                    player.redraw_bg = false;
+                }
             }
-            GotInput => *state = FadeOutTextAndActions(Timer::from_seconds(0.5, false)),
+            GotInput => *state = FadeOutTextAndActions(player.phase(0.5)),
             FadeOutTextAndActions(ref mut timer) => {
                 player.percentage_of_text_shown = 1.0 - timer.percent();
                 player.amount_of_actions_shown = 1.0 - timer.percent();
@@ -131,7 +262,7 @@ impl Player {
                     player.redraw_bg = true;
                     player.percentage_of_text_shown = 0.0;
                     player.amount_of_actions_shown = 0.0;
-                    *state = FadeOutBg(Timer::from_seconds(BG_FADE_OUT, false))
+                    *state = FadeOutBg(player.phase(BG_FADE_OUT))
                 }
             }
             FadeOutBg(ref mut timer) => {
@@ -139,8 +270,10 @@ impl Player {
                 if timer.tick(time.delta()).just_finished() {
                     info!("FadeOutBg finished");
                     player.bg_opacity = 0.0;
-                    player.current_slide = player.next_slide.clone();
-                    *state = FadeInBg(Timer::from_seconds(BG_FADE_IN, false))
+                    player.hovering_action = None;
+                    let next = player.next_slide.clone();
+                    player.progress.go_to(next);
+                    *state = FadeInBg(player.phase(BG_FADE_IN))
                 }
             }
             _ => {}
@@ -153,14 +286,28 @@ const BG_FADE_OUT: f32 = 2.0;
 const MEAN_WORD_LENGTH: f32 = 4.7;
 const MEAN_READING_SPEED_WPS: f32 = 4.0;
 
+const SKIP_KEY: KeyCode = KeyCode::S;
+const ACTION_KEYS: [KeyCode; 9] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
+
 impl Player {
     fn new() -> Self {
         Self {
-            current_slide: "Living".into(),
-            next_slide: "Living".into(),
+            progress: Progress::default(),
+            next_slide: String::new(),
             render_timer: Timer::from_seconds(0.1, true),
             render: true,
             redraw_bg: true,
+            bg_key: None,
             pauses: 0.1, // 1.0
             dashes: 0,
             percentage_of_text_shown: 0.0,
@@ -168,8 +315,133 @@ impl Player {
             hovering_action: None,
             bg_opacity: 0.0,
             action_pause: 0.1,  // 1.0
+            settings: PlayerSettings::load(),
+        }
+    }
+
+    /// Timer for a phase of the given length at the reader's text speed.
+    fn phase(&self, seconds: f32) -> Timer {
+        Timer::from_seconds(seconds / self.settings.text_speed.max(0.01), false)
+    }
+
+    fn choose(
+        &mut self,
+        action: &Action,
+        game_state: &mut GameState,
+        player_state: &mut PlayerState,
+    ) {
+        self.next_slide = self.progress.choose(action, game_state);
+        self.hovering_action = None;
+        *player_state = PlayerState::GotInput;
+    }
+
+    /// Finishes whatever is fading or being typed, leaving the current slide,
+    /// or the one being left for, waiting for a choice.
+    fn skip(&mut self, player_state: &mut PlayerState) {
+        use PlayerState::*;
+        match player_state {
+            WaitingForInput(_) => return,
+            GotInput | FadeOutTextAndActions(_) | FadeOutBg(_) => {
+                self.progress.go_to(self.next_slide.clone());
+                self.hovering_action = None;
+            }
+            _ => {}
+        }
+        self.bg_opacity = 1.0;
+        self.percentage_of_text_shown = 1.0;
+        self.amount_of_actions_shown = 1.0;
+        self.redraw_bg = true;
+        *player_state = WaitingForInput(Timer::from_seconds(1.0, true));
+    }
+
+    pub(crate) fn history(&self) -> &[HistoryEntry] {
+        &self.progress.history
+    }
+
+    pub(crate) fn can_step_back(&self) -> bool {
+        self.progress.can_step_back()
+    }
+
+    /// Goes back to the slide of the last choice, as it was before choosing.
+    pub(crate) fn step_back(&mut self, game_state: &mut GameState, player_state: &mut PlayerState) {
+        if self.progress.step_back(game_state) {
+            self.next_slide = self.progress.current_slide.clone();
+            self.replay_entrance(player_state);
+        }
+    }
+
+    pub(crate) fn save_game(&self, game_state: &GameState) -> SaveGame {
+        SaveGame {
+            current_slide: self.progress.current_slide.clone(),
+            history: self.progress.history.clone(),
+            state: game_state.clone(),
+        }
+    }
+
+    /// Jumps to the saved slide and replays its entrance from the start.
+    pub(crate) fn load_game(
+        &mut self,
+        save: SaveGame,
+        game_state: &mut GameState,
+        player_state: &mut PlayerState,
+    ) {
+        self.next_slide = save.current_slide.clone();
+        self.progress = Progress {
+            // The saved state already contains the effects of entering the slide.
+            entered_slide: Some(save.current_slide.clone()),
+            current_slide: save.current_slide,
+            history: save.history,
+        };
+        *game_state = save.state;
+        self.replay_entrance(player_state);
+    }
+
+    /// Starts the story over at the slide, for a story that was just opened.
+    pub(crate) fn start_at(
+        &mut self,
+        slide: &str,
+        game_state: &mut GameState,
+        player_state: &mut PlayerState,
+    ) {
+        self.next_slide = slide.into();
+        self.progress = Progress::starting_at(slide);
+        *game_state = GameState::default();
+        self.replay_entrance(player_state);
+    }
+
+    /// Reader settings, for the menu players open with Esc. Saved as they
+    /// are changed.
+    pub(crate) fn settings_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Up/Down and Enter or 1-9 choose an action, S skips ahead.");
+        ui.label("A click or Enter finishes the current fade.");
+        let before = self.settings.clone();
+        let speed = ui.add(
+            egui::Slider::new(&mut self.settings.text_speed, 0.25..=4.0).text("Text speed"),
+        );
+        // Saved once the slider is let go of, not on every step of a drag.
+        let speed_set = speed.drag_released()
+            || speed.lost_focus()
+            || (speed.changed() && !speed.dragged());
+        ui.checkbox(&mut self.settings.skip_seen, "Show seen slides instantly");
+        ui.checkbox(&mut self.settings.colored_backgrounds, "Colored backgrounds");
+        if self.settings.colored_backgrounds != before.colored_backgrounds {
+            self.redraw_bg = true;
+        }
+        let toggled = self.settings.skip_seen != before.skip_seen
+            || self.settings.colored_backgrounds != before.colored_backgrounds;
+        if speed_set || toggled {
+            self.settings.save();
         }
     }
+
+    fn replay_entrance(&mut self, player_state: &mut PlayerState) {
+        self.hovering_action = None;
+        self.bg_opacity = 0.0;
+        self.percentage_of_text_shown = 0.0;
+        self.amount_of_actions_shown = 0.0;
+        self.redraw_bg = true;
+        *player_state = PlayerState::FadeInBg(self.phase(BG_FADE_IN));
+    }
 }
 
 fn window_to_world(
@@ -202,14 +474,17 @@ impl Player {
         windows: Res<Windows>,
         time: Res<Time>,
         slides: Query<&Slide>,
+        mut game_state: ResMut<GameState>,
+        egui_context: ResMut<EguiContext>,
     ) {
         let slide = slides
             .iter()
-            .find(|slide| slide.name == player.current_slide);
+            .find(|slide| slide.id == player.progress.current_slide);
         if slide.is_none() {
             return;
         }
         let slide = slide.unwrap();
+        let actions = slide.available_actions(&game_state);
         let offset_between_actions = 30.0;
         let mut action_origin = 0.0;
         let min_distance = 30;
@@ -221,7 +496,7 @@ impl Player {
             let window = windows.get(*id).unwrap();
             let position = window_to_world(window, camera, position);
             let mut distances = vec![];
-            for (i, _a) in slide.actions.iter().enumerate() {
+            for (i, _a) in actions.iter().enumerate() {
                 let d =
                     (action_origin - i as f32 * offset_between_actions - position.y).abs() as i32;
                 if d < min_distance {
@@ -235,28 +510,88 @@ impl Player {
         }
         for MouseButtonInput { button, state } in mouse_button.iter() {
             if *button == MouseButton::Left && state.is_pressed() {
-
-                if let Some(i) = player.hovering_action.clone() {
-                    let a = slide.actions.get(i).unwrap();
-                    player.next_slide = a.target_slide.clone();
-                    *player_state = PlayerState::GotInput;
+                if egui_context.ctx().wants_pointer_input() {
+                    continue;
+                }
+                if !matches!(*player_state, PlayerState::WaitingForInput(_)) {
+                    player_state.finish_phase();
+                    continue;
+                }
+                if let Some(a) = player.hovering_action.and_then(|i| actions.get(i)) {
+                    player.choose(a, &mut game_state, &mut player_state);
                 }
             }
         }
     }
 
-    fn handle_renames(mut player: ResMut<Self>, mut slide_events: EventReader<CrudEvent<Slide>>) {
-        for ev in slide_events.iter() {
-            match ev {
-                CrudEvent::Renamed(old_name, new_name) => {
-                    if player.current_slide == *old_name {
-                        player.current_slide = new_name.clone();
-                    }
-                }
-                _ => {}
+    fn handle_keys(
+        keys: Res<Input<KeyCode>>,
+        egui_context: ResMut<EguiContext>,
+        mut player: ResMut<Self>,
+        mut player_state: ResMut<PlayerState>,
+        slides: Query<&Slide>,
+        mut game_state: ResMut<GameState>,
+    ) {
+        if egui_context.ctx().wants_keyboard_input() {
+            return;
+        }
+        if keys.just_pressed(SKIP_KEY) {
+            player.skip(&mut player_state);
+            return;
+        }
+        let confirm = keys.just_pressed(KeyCode::Return) || keys.just_pressed(KeyCode::Space);
+        if !matches!(*player_state, PlayerState::WaitingForInput(_)) {
+            if confirm {
+                player_state.finish_phase();
             }
+            return;
+        }
+        let slide = match slides
+            .iter()
+            .find(|slide| slide.id == player.progress.current_slide)
+        {
+            None => return,
+            Some(s) => s,
+        };
+        let actions = slide.available_actions(&game_state);
+        if actions.is_empty() {
+            return;
+        }
+        let last = actions.len() - 1;
+        if keys.just_pressed(KeyCode::Down) {
+            player.hovering_action = Some(match player.hovering_action {
+                None => 0,
+                Some(i) => (i + 1).min(last),
+            });
+        }
+        if keys.just_pressed(KeyCode::Up) {
+            player.hovering_action = Some(match player.hovering_action {
+                None => last,
+                Some(i) => i.saturating_sub(1),
+            });
+        }
+        let chosen = if confirm {
+            player.hovering_action
+        } else {
+            ACTION_KEYS.iter().position(|k| keys.just_pressed(*k))
+        };
+        if let Some(a) = chosen.and_then(|i| actions.get(i)) {
+            player.choose(a, &mut game_state, &mut player_state);
+        }
+    }
+
+    /// Unless a save game was resumed, begin at the manifest's start slide.
+    /// Only once it changed, an opened project's is not filed in right away.
+    fn start_from_manifest(mut player: ResMut<Self>, manifest: Res<StoryManifest>) {
+        if manifest.is_changed()
+            && player.progress.current_slide.is_empty()
+            && !manifest.start_slide.is_empty()
+        {
+            player.progress.current_slide = manifest.start_slide.clone();
+            player.next_slide = manifest.start_slide.clone();
         }
     }
+
     fn startup(mut commands: Commands, asset_server: Res<AssetServer>) {
         info!("Player starting up");
         commands
@@ -325,42 +660,135 @@ impl Player {
                 ..Default::default()
             })
             .insert(DisplayActions);
+        commands
+            .spawn_bundle(Text2dBundle {
+                text: Text::with_section(
+                    "",
+                    TextStyle {
+                        font: asset_server.load("fonts/BPtypewrite.otf"),
+                        font_size: 12.0,
+                        color: Color::WHITE,
+                    },
+                    TextAlignment {
+                        vertical: VerticalAlign::Bottom,
+                        horizontal: HorizontalAlign::Left,
+                    },
+                ),
+                transform: Transform {
+                    translation: Vec3::new(420.0, -30.0, 0.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert(DisplayInventory);
     }
 
     fn render(
         mut player: ResMut<Self>,
         slides: Query<&Slide>,
+        game_state: Res<GameState>,
+        items: Query<&Item>,
         time: Res<Time>,
-        backgrounds: Query<(&Background, &BackgroundData)>,
+        backgrounds: Query<(&Background, Option<&BackgroundData>, Option<&LoadStatus>)>,
         mut texts: QuerySet<(
             Query<&mut Text, With<DisplayBackground>>,
             Query<&mut Text, With<DisplayDescription>>,
             Query<&mut Text, With<DisplayActions>>,
+            Query<&mut Text, With<DisplayInventory>>,
         )>,
         mut commands: Commands,
     ) {
         if !player.render_timer.tick(time.delta()).just_finished() || !player.render {
             return;
         }
+
+        let mut inventory_text = String::new();
+        if !game_state.inventory.is_empty() {
+            inventory_text += "Inventory:\n\n";
+        }
+        for name in game_state.inventory.iter() {
+            if let Some(item) = items.iter().find(|i| i.name == *name) {
+                if !item.icon.is_empty() {
+                    inventory_text += &item.icon;
+                    inventory_text += "\n";
+                }
+            }
+            inventory_text += name;
+            inventory_text += "\n\n";
+        }
+        for mut t in texts.q3_mut().iter_mut() {
+            if t.sections.first().unwrap().value != inventory_text {
+                t.sections.first_mut().unwrap().value = inventory_text.clone();
+            }
+        }
         match slides
             .iter()
-            .find(|slide| slide.name == player.current_slide)
+            .find(|slide| slide.id == player.progress.current_slide)
         {
             None => {
                 warn!("slide not found");
+                let message = if player.progress.current_slide.is_empty() {
+                    "Can not start the story: no start slide is set in the story manifest."
+                        .to_string()
+                } else {
+                    format!(
+                        "Can not start the story: the slide \"{}\" does not exist.",
+                        player.progress.current_slide
+                    )
+                };
+                for mut t in texts.q1_mut().iter_mut() {
+                    if t.sections.first().unwrap().value != message {
+                        t.sections.first_mut().unwrap().value = message.clone();
+                    }
+                }
             }
             Some(slide) => {
-                match backgrounds
+                let colored = player.settings.colored_backgrounds;
+                let shown_bg = match backgrounds
                     .iter()
-                    .find(|(bg, _)| bg.name() == slide.background)
+                    .find(|(bg, _, _)| bg.id() == slide.background)
                 {
-                    None => warn!("background not found"),
+                    None => {
+                        warn!("background not found");
+                        None
+                    }
+                    Some((bg, Some(bgd), _)) => Some((bg, Some(bgd))),
+                    // Better to go on with the story than to wait for an
+                    // image that is never coming.
+                    Some((bg, None, Some(LoadStatus::Failed(_)))) => Some((bg, None)),
+                    Some(_) => None,
+                };
+                match shown_bg {
+                    None => {}
                     Some((bg, bgd)) => {
-                        if player.redraw_bg  {
-                            let rendered_text = convert_background_to_ascii(bg, bgd, player.bg_opacity);
-                            for mut t in texts.q0_mut().iter_mut() {
-                                if t.sections.first().unwrap().value != rendered_text {
-                                    t.sections.first_mut().unwrap().value = rendered_text.clone();
+                        if player.redraw_bg {
+                            match bgd {
+                                Some(bgd) if colored => {
+                                    let key = (bg.clone(), bgd.texture_id());
+                                    if player.bg_key.as_ref() != Some(&key) {
+                                        let ascii =
+                                            convert_background_to_colored_ascii(bg, bgd, 1.0);
+                                        for mut t in texts.q0_mut().iter_mut() {
+                                            set_background_sections(&mut t, &ascii);
+                                        }
+                                        player.bg_key = Some(key);
+                                    }
+                                    let opacity = player.bg_opacity;
+                                    for mut t in texts.q0_mut().iter_mut() {
+                                        set_background_opacity(&mut t, opacity);
+                                    }
+                                }
+                                _ => {
+                                    player.bg_key = None;
+                                    let rendered_text = match bgd {
+                                        Some(bgd) => {
+                                            convert_background_to_ascii(bg, bgd, player.bg_opacity)
+                                        }
+                                        None => placeholder_ascii(&format!("[ {} ]", bg.name())),
+                                    };
+                                    for mut t in texts.q0_mut().iter_mut() {
+                                        set_background_text(&mut t, &rendered_text);
+                                    }
                                 }
                             }
                         }
@@ -377,9 +805,10 @@ impl Player {
                         }
 
                         let mut actions_text = String::new();
+                        let actions = slide.available_actions(&game_state);
                         let n_actions =
-                            (slide.actions.len() as f32 * player.amount_of_actions_shown) as usize;
-                        for (i, a) in slide.actions.iter().enumerate().take(n_actions) {
+                            (actions.len() as f32 * player.amount_of_actions_shown) as usize;
+                        for (i, a) in actions.iter().enumerate().take(n_actions) {
                             if player.hovering_action == Some(i) {
                                 actions_text += &"-".repeat(player.dashes);
                                 actions_text += "> ";
@@ -407,6 +836,7 @@ impl Player {
     fn render_controls(
         mut player: ResMut<Self>,
         mut player_state: ResMut<PlayerState>,
+        mut game_state: ResMut<GameState>,
         slides: Query<&Slide>,
         egui_context: ResMut<EguiContext>,
         mut texts: Query<
@@ -418,14 +848,25 @@ impl Player {
             )>,
         >,
         mut commands: Commands,
+        editors_open: Res<EditorsOpen>,
     ) {
-        let valid_slide_names: Vec<_> = slides.iter().map(|s| s.name.clone()).collect();
+        if !editors_open.0 {
+            return;
+        }
+        let current_name = slides
+            .iter()
+            .find(|s| s.id == player.progress.current_slide)
+            .map_or(player.progress.current_slide.clone(), |s| s.name.clone());
         egui::Window::new("Player Controls").show(egui_context.ctx(), |ui| {
             egui::ComboBox::from_label("Current slide")
-                .selected_text(&player.current_slide)
+                .selected_text(current_name)
                 .show_ui(ui, |ui| {
-                    for sn in valid_slide_names.iter() {
-                        ui.selectable_value(&mut player.current_slide, sn.clone(), sn);
+                    for s in slides.iter() {
+                        ui.selectable_value(
+                            &mut player.progress.current_slide,
+                            s.id.clone(),
+                            &s.name,
+                        );
                     }
                 });
             ui.separator();
@@ -450,7 +891,7 @@ impl Player {
 
             let s = slides
                 .iter()
-                .find(|slide| slide.name == player.current_slide);
+                .find(|slide| slide.id == player.progress.current_slide);
             if s.is_none() {
                 ui.colored_label(egui::Color32::RED, "The current slide does not exist");
                 return;
@@ -462,11 +903,11 @@ impl Player {
                     .enabled(false),
             );
             ui.separator();
-            for (i, a) in scene.actions.iter().enumerate() {
+            let waiting = matches!(*player_state, PlayerState::WaitingForInput(_));
+            for (i, a) in scene.available_actions(&game_state).into_iter().enumerate() {
                 let res = ui.button(&a.text);
-                if res.clicked() {
-                    player.next_slide = a.target_slide.clone();
-                    *player_state = PlayerState::GotInput;
+                if res.clicked() && waiting {
+                    player.choose(a, &mut game_state, &mut player_state);
                 }
                 if res.hovered() {
                     player.hovering_action = Some(i);
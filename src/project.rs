@@ -0,0 +1,377 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "editor")]
+use bevy::prelude::*;
+#[cfg(feature = "editor")]
+use bevy_egui::{egui, EguiContext};
+#[cfg(feature = "editor")]
+use serde::*;
+
+use crate::{
+    images::{Background, CACHE_DIR},
+    persistence::PersistenceError,
+    saves::SAVES_DIR,
+};
+#[cfg(feature = "editor")]
+use crate::{
+    game_state::GameState,
+    items::Item,
+    manifest::StoryManifest,
+    model::{EditorsOpen, Slide},
+    persistence::{load_settings, save_settings, PersistenceEvent, PersistenceResult},
+    player::{Player, PlayerState},
+    saves::resume_latest,
+    unsaved::{self, UnsavedChanges},
+};
+
+/// A story on disk: a directory with the slides, backgrounds, items and
+/// manifest files, along with their backups, the save games and the exports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Project {
+    pub dir: PathBuf,
+}
+
+impl Project {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// The directory given with `--project <dir>`, otherwise the working
+    /// directory, where the story files were before projects existed.
+    pub fn from_args() -> Self {
+        let args: Vec<String> = env::args().collect();
+        let dir = args
+            .iter()
+            .position(|arg| arg == "--project")
+            .and_then(|i| args.get(i + 1))
+            .map_or(PathBuf::from("."), PathBuf::from);
+        Self::new(dir)
+    }
+
+    pub fn path(&self, file_name: &str) -> PathBuf {
+        self.dir.join(file_name)
+    }
+
+    /// Name of the directory, for showing to the author.
+    pub fn name(&self) -> String {
+        let dir = fs::canonicalize(&self.dir).unwrap_or_else(|_| self.dir.clone());
+        dir.file_name()
+            .map_or_else(|| dir.display().to_string(), |n| n.to_string_lossy().into())
+    }
+
+    /// Copies what the story needs besides its own files to the project in
+    /// `to`: the backgrounds kept in the project directory, the image cache
+    /// and the save games. Gives what could not be copied.
+    pub fn copy_assets_to(&self, to: &Project, backgrounds: &[Background]) -> Vec<PersistenceError> {
+        let same = match (fs::canonicalize(&self.dir), fs::canonicalize(&to.dir)) {
+            (Ok(from), Ok(to)) => from == to,
+            _ => self.dir == to.dir,
+        };
+        if same {
+            return vec![];
+        }
+        let mut paths: Vec<&Path> = backgrounds.iter().filter_map(Background::path_in_project).collect();
+        paths.push(Path::new(CACHE_DIR));
+        paths.push(Path::new(SAVES_DIR));
+        let mut errors = vec![];
+        for path in paths {
+            copy_all(&self.dir.join(path), &to.dir.join(path), &mut errors);
+        }
+        errors
+    }
+
+    /// An empty project in the temporary directory, for tests that go to disk.
+    #[cfg(test)]
+    pub(crate) fn temp(name: &str) -> Self {
+        let dir = env::temp_dir().join(format!("fni2stib8-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Self::new(dir)
+    }
+}
+
+/// Copies the file, or the directory with everything in it. Missing ones are
+/// left out.
+fn copy_all(from: &Path, to: &Path, errors: &mut Vec<PersistenceError>) {
+    let copied = if from.is_dir() {
+        fs::create_dir_all(to).and_then(|_| fs::read_dir(from)).map(|entries| {
+            for entry in entries.filter_map(|entry| entry.ok()) {
+                copy_all(&entry.path(), &to.join(entry.file_name()), errors);
+            }
+        })
+    } else if from.is_file() {
+        to.parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::copy(from, to))
+            .map(|_| ())
+    } else {
+        Ok(())
+    };
+    if let Err(e) = copied {
+        errors.push(PersistenceError::io(to, e));
+    }
+}
+
+#[cfg(feature = "editor")]
+const RECENT_PATH: &str = "recent_projects.json";
+#[cfg(feature = "editor")]
+const RECENT_KEPT: usize = 10;
+
+/// Project directories opened lately, the latest first. Kept next to the
+/// player settings, not in any project.
+#[cfg(feature = "editor")]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+struct RecentProjects(Vec<PathBuf>);
+
+#[cfg(feature = "editor")]
+impl RecentProjects {
+    fn load() -> Self {
+        load_settings(RECENT_PATH, "recent projects")
+    }
+
+    fn save(&self) {
+        save_settings(RECENT_PATH, self, "recent projects");
+    }
+
+    fn remember(&mut self, dir: &Path) {
+        let dir = fs::canonicalize(dir).unwrap_or_else(|_| dir.into());
+        self.0.retain(|d| *d != dir);
+        self.0.insert(0, dir);
+        self.0.truncate(RECENT_KEPT);
+        self.save();
+    }
+}
+
+#[cfg(feature = "editor")]
+pub struct ProjectPlugin;
+
+#[cfg(feature = "editor")]
+impl Plugin for ProjectPlugin {
+    fn build(&self, builder: &mut AppBuilder) {
+        builder
+            .insert_resource(RecentProjects::load())
+            .add_event::<OpenProject>()
+            .add_startup_system(remember_project.system())
+            .add_system(open_project.system())
+            .add_system(ProjectDialog::render.system());
+    }
+}
+
+#[cfg(feature = "editor")]
+fn remember_project(project: Res<Project>, mut recent: ResMut<RecentProjects>) {
+    recent.remember(&project.dir);
+}
+
+/// Switches to the project in the directory and files its story in, whatever
+/// is unsaved is lost by then. The player resumes the project's last save or
+/// starts its story over.
+#[cfg(feature = "editor")]
+pub struct OpenProject(pub PathBuf);
+
+#[cfg(feature = "editor")]
+fn open_project(
+    mut events: EventReader<OpenProject>,
+    mut project: ResMut<Project>,
+    mut recent: ResMut<RecentProjects>,
+    mut player: ResMut<Player>,
+    mut player_state: ResMut<PlayerState>,
+    mut game_state: ResMut<GameState>,
+    mut slide_persistence: EventWriter<PersistenceEvent<Slide>>,
+    mut bg_persistence: EventWriter<PersistenceEvent<Background>>,
+    mut item_persistence: EventWriter<PersistenceEvent<Item>>,
+    mut manifest_persistence: EventWriter<PersistenceEvent<StoryManifest>>,
+    mut results: EventWriter<PersistenceResult>,
+) {
+    for OpenProject(dir) in events.iter() {
+        info!("Opening project {}", dir.display());
+        recent.remember(dir);
+        *project = Project::new(dir.clone());
+        slide_persistence.send(PersistenceEvent::FileIn);
+        bg_persistence.send(PersistenceEvent::FileIn);
+        item_persistence.send(PersistenceEvent::FileIn);
+        manifest_persistence.send(PersistenceEvent::FileIn);
+        if !resume_latest(
+            &project,
+            &mut player,
+            &mut game_state,
+            &mut player_state,
+            &mut results,
+        ) {
+            // Read here, the manifest resource still holds the old story's.
+            let start = match StoryManifest::load(&project) {
+                Ok(manifest) => manifest.start_slide,
+                Err(e) => {
+                    warn!("{}", e);
+                    String::new()
+                }
+            };
+            player.start_at(&start, &mut game_state, &mut player_state);
+        }
+    }
+}
+
+#[cfg(feature = "editor")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProjectAction {
+    Open,
+    SaveAs,
+}
+
+/// Picks a directory to open the story from or to write it to.
+#[cfg(feature = "editor")]
+pub struct ProjectDialog {
+    action: ProjectAction,
+    dir: String,
+    info: String,
+}
+
+#[cfg(feature = "editor")]
+impl ProjectDialog {
+    pub fn open(current: &Project) -> Self {
+        Self {
+            action: ProjectAction::Open,
+            dir: current.dir.display().to_string(),
+            info: String::new(),
+        }
+    }
+
+    pub fn save_as(current: &Project) -> Self {
+        Self {
+            action: ProjectAction::SaveAs,
+            ..Self::open(current)
+        }
+    }
+
+    fn render(
+        egui_context: ResMut<EguiContext>,
+        dialog: Option<ResMut<Self>>,
+        mut project: ResMut<Project>,
+        mut recent: ResMut<RecentProjects>,
+        mut slide_persistence: EventWriter<PersistenceEvent<Slide>>,
+        mut bg_persistence: EventWriter<PersistenceEvent<Background>>,
+        mut item_persistence: EventWriter<PersistenceEvent<Item>>,
+        mut manifest_persistence: EventWriter<PersistenceEvent<StoryManifest>>,
+        mut open_events: EventWriter<OpenProject>,
+        mut results: EventWriter<PersistenceResult>,
+        backgrounds: Query<&Background>,
+        unsaved: Res<UnsavedChanges>,
+        mut commands: Commands,
+        editors_open: Res<EditorsOpen>,
+    ) {
+        let mut dialog = match dialog {
+            Some(d) if editors_open.0 => d,
+            _ => return,
+        };
+        let title = match dialog.action {
+            ProjectAction::Open => "Open project",
+            ProjectAction::SaveAs => "Save project as",
+        };
+        egui::Window::new(title).show(egui_context.ctx(), |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Directory:");
+                ui.text_edit_singleline(&mut dialog.dir);
+            });
+            if !recent.0.is_empty() {
+                ui.label("Recent:");
+                for dir in recent.0.iter() {
+                    let shown = dir.display().to_string();
+                    if ui.selectable_label(dialog.dir == shown, &shown).clicked() {
+                        dialog.dir = shown;
+                    }
+                }
+            }
+            ui.separator();
+            if dialog.action == ProjectAction::SaveAs {
+                ui.label("Story files already there are overwritten, with a backup.");
+                ui.label("Images kept in the project and save games are copied along.");
+            }
+            ui.horizontal(|ui| {
+                if ui.button("Cancel").clicked() {
+                    commands.remove_resource::<Self>();
+                }
+                if ui.button(title).clicked() {
+                    let dir = PathBuf::from(dialog.dir.trim());
+                    match dialog.action {
+                        ProjectAction::Open if !dir.is_dir() => {
+                            dialog.info = "There is no such directory".into();
+                        }
+                        ProjectAction::Open => {
+                            unsaved::open_project(dir, &unsaved, &mut commands, &mut open_events);
+                            commands.remove_resource::<Self>();
+                        }
+                        ProjectAction::SaveAs => match fs::create_dir_all(&dir) {
+                            Err(e) => dialog.info = e.to_string(),
+                            Ok(()) => {
+                                info!("Saving the story to project {}", dir.display());
+                                recent.remember(&dir);
+                                let saved_as = Project::new(dir);
+                                let backgrounds: Vec<_> = backgrounds.iter().cloned().collect();
+                                for e in project.copy_assets_to(&saved_as, &backgrounds) {
+                                    results.send(PersistenceResult::Failed(e));
+                                }
+                                *project = saved_as;
+                                slide_persistence.send(PersistenceEvent::FileOut);
+                                bg_persistence.send(PersistenceEvent::FileOut);
+                                item_persistence.send(PersistenceEvent::FileOut);
+                                manifest_persistence.send(PersistenceEvent::FileOut);
+                                commands.remove_resource::<Self>();
+                            }
+                        },
+                    }
+                }
+            });
+            if !dialog.info.is_empty() {
+                ui.colored_label(egui::Color32::RED, &dialog.info);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn background(url: &str) -> Background {
+        serde_json::from_value(serde_json::json!({
+            "id": url,
+            "name": url,
+            "url": url,
+            "color_channels": [255, 255, 255],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn copies_assets_kept_in_the_project() {
+        let from = Project::temp("assets_from");
+        let to = Project::temp("assets_to");
+        fs::create_dir_all(from.path("images")).unwrap();
+        fs::write(from.path("images").join("hall.png"), "hall").unwrap();
+        fs::write(from.path("yard.png"), "yard").unwrap();
+        fs::write(from.path("unused.png"), "unused").unwrap();
+        fs::create_dir_all(from.path(SAVES_DIR)).unwrap();
+        fs::write(from.path(SAVES_DIR).join("quicksave.json"), "{}").unwrap();
+        let backgrounds = vec![
+            background("images/hall.png"),
+            background("file://yard.png"),
+            background("https://example.com/sky.png"),
+            background("../outside.png"),
+            background("missing.png"),
+        ];
+
+        assert_eq!(from.copy_assets_to(&to, &backgrounds), vec![]);
+        assert_eq!(fs::read_to_string(to.path("images").join("hall.png")).unwrap(), "hall");
+        assert_eq!(fs::read_to_string(to.path("yard.png")).unwrap(), "yard");
+        assert_eq!(fs::read_to_string(to.path(SAVES_DIR).join("quicksave.json")).unwrap(), "{}");
+        assert!(!to.path("unused.png").exists());
+        assert!(!to.path(CACHE_DIR).exists());
+        // Copying onto itself would empty the files.
+        assert_eq!(from.copy_assets_to(&from, &backgrounds), vec![]);
+        assert_eq!(fs::read_to_string(from.path("yard.png")).unwrap(), "yard");
+        fs::remove_dir_all(&from.dir).unwrap();
+        fs::remove_dir_all(&to.dir).unwrap();
+    }
+}
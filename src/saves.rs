@@ -0,0 +1,343 @@
+use serde::*;
+use std::{fs, path::PathBuf, time::SystemTime};
+
+#[cfg(feature = "editor")]
+use bevy::{input::keyboard::KeyboardInput, prelude::*};
+#[cfg(feature = "editor")]
+use bevy_egui::{egui, EguiContext};
+use tracing::info;
+
+use crate::{
+    game_state::GameState,
+    persistence::{read_json, write_sidecar, PersistenceError},
+    project::Project,
+};
+#[cfg(feature = "editor")]
+use crate::{
+    persistence::PersistenceResult,
+    player::{Player, PlayerState},
+};
+
+/// Within the project directory, saves belong to the story they are of.
+pub(crate) const SAVES_DIR: &str = "saves";
+#[cfg(feature = "editor")]
+const QUICKSAVE_SLOT: &str = "quicksave";
+
+#[cfg(feature = "editor")]
+pub struct SavesPlugin;
+
+#[cfg(feature = "editor")]
+impl Plugin for SavesPlugin {
+    fn build(&self, builder: &mut AppBuilder) {
+        builder
+            .add_startup_system(resume_last_save.system())
+            .add_system(SaveMenu::handle_keys.system())
+            .add_system(SaveMenu::render.system());
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Id of the slide, saves from before ids existed hold its name, which
+    /// is what those slides got as id.
+    pub slide: String,
+    pub action: String,
+    /// Game state right before the action was taken, to be able to go back.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<GameState>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct SaveGame {
+    /// Id of the slide, like in `HistoryEntry`.
+    pub current_slide: String,
+    #[serde(default)]
+    pub history: Vec<HistoryEntry>,
+    #[serde(default)]
+    pub state: GameState,
+}
+
+fn slot_path(project: &Project, slot: &str) -> PathBuf {
+    project.path(SAVES_DIR).join(format!("{}.json", slot))
+}
+
+fn validate_slot(slot: &str) -> Result<(), &'static str> {
+    if slot.trim().is_empty() {
+        return Err("Slot name can not be empty");
+    }
+    if !slot
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == ' ')
+    {
+        return Err("Slot names can only have letters, numbers, spaces, - and _");
+    }
+    Ok(())
+}
+
+/// Existing save slots, most recently written first.
+pub fn list_slots(project: &Project) -> Vec<(String, SystemTime)> {
+    let mut slots: Vec<_> = match fs::read_dir(project.path(SAVES_DIR)) {
+        Err(_) => return vec![],
+        Ok(dir) => dir
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().map_or(false, |ext| ext == "json"))
+            .filter_map(|path| {
+                let slot = path.file_stem()?.to_str()?.to_string();
+                let modified = fs::metadata(&path).ok()?.modified().ok()?;
+                Some((slot, modified))
+            })
+            .collect(),
+    };
+    slots.sort_by_key(|(_, modified)| std::cmp::Reverse(*modified));
+    slots
+}
+
+pub fn write_slot(
+    project: &Project,
+    slot: &str,
+    save: &SaveGame,
+) -> Result<(), PersistenceError> {
+    let path = slot_path(project, slot);
+    validate_slot(slot).map_err(|message| PersistenceError {
+        path: path.clone(),
+        message: message.into(),
+        position: None,
+    })?;
+    let dir = project.path(SAVES_DIR);
+    fs::create_dir_all(&dir).map_err(|e| PersistenceError::io(&dir, e))?;
+    write_sidecar(&path, save)?;
+    info!("Saved game to slot {}", slot);
+    Ok(())
+}
+
+pub fn read_slot(project: &Project, slot: &str) -> Result<SaveGame, PersistenceError> {
+    read_json(&slot_path(project, slot))
+}
+
+#[cfg(feature = "editor")]
+fn resume_last_save(
+    mut player: ResMut<Player>,
+    mut player_state: ResMut<PlayerState>,
+    mut game_state: ResMut<GameState>,
+    project: Res<Project>,
+    mut results: EventWriter<PersistenceResult>,
+) {
+    resume_latest(&project, &mut player, &mut game_state, &mut player_state, &mut results);
+}
+
+/// Loads the slot written last into the player, gives whether there was one
+/// to load.
+#[cfg(feature = "editor")]
+pub(crate) fn resume_latest(
+    project: &Project,
+    player: &mut Player,
+    game_state: &mut GameState,
+    player_state: &mut PlayerState,
+    results: &mut EventWriter<PersistenceResult>,
+) -> bool {
+    let (slot, _) = match list_slots(project).into_iter().next() {
+        None => return false,
+        Some(s) => s,
+    };
+    match read_slot(project, &slot) {
+        Ok(save) => {
+            info!("Resuming from slot {}", slot);
+            player.load_game(save, game_state, player_state);
+            true
+        }
+        Err(e) => {
+            warn!("Could not resume from slot {}", slot);
+            results.send(PersistenceResult::Failed(e));
+            false
+        }
+    }
+}
+
+#[cfg(feature = "editor")]
+/// Opened with Esc, for save games and the reader settings.
+#[derive(Debug, Default)]
+struct SaveMenu {
+    slot: String,
+    info: String,
+    /// Read when the menu opens and again after every save.
+    slots: Option<Vec<(String, SystemTime)>>,
+}
+
+#[cfg(feature = "editor")]
+impl SaveMenu {
+    fn handle_keys(
+        mut keys: EventReader<KeyboardInput>,
+        mut menu: Option<ResMut<Self>>,
+        egui_context: ResMut<EguiContext>,
+        mut player: ResMut<Player>,
+        mut player_state: ResMut<PlayerState>,
+        mut game_state: ResMut<GameState>,
+        project: Res<Project>,
+        mut results: EventWriter<PersistenceResult>,
+        mut commands: Commands,
+    ) {
+        if egui_context.ctx().wants_keyboard_input() {
+            // Typed into a text field, not shortcuts for later.
+            for _ in keys.iter() {}
+            return;
+        }
+        for ev in keys.iter() {
+            if !ev.state.is_pressed() {
+                continue;
+            }
+            match ev.key_code {
+                Some(KeyCode::Escape) => {
+                    if menu.is_some() {
+                        commands.remove_resource::<Self>();
+                    } else {
+                        commands.insert_resource(Self::default());
+                    }
+                }
+                Some(KeyCode::F5) => {
+                    let save = player.save_game(&game_state);
+                    if let Err(e) = write_slot(&project, QUICKSAVE_SLOT, &save) {
+                        results.send(PersistenceResult::Failed(e));
+                    }
+                    if let Some(menu) = menu.as_mut() {
+                        menu.slots = None;
+                    }
+                }
+                Some(KeyCode::F9) => match read_slot(&project, QUICKSAVE_SLOT) {
+                    Ok(save) => player.load_game(save, &mut game_state, &mut player_state),
+                    Err(e) => results.send(PersistenceResult::Failed(e)),
+                },
+                _ => {}
+            }
+        }
+    }
+
+    fn render(
+        egui_context: ResMut<EguiContext>,
+        menu: Option<ResMut<Self>>,
+        mut player: ResMut<Player>,
+        mut player_state: ResMut<PlayerState>,
+        mut game_state: ResMut<GameState>,
+        project: Res<Project>,
+        mut results: EventWriter<PersistenceResult>,
+        mut commands: Commands,
+    ) {
+        let mut menu = match menu {
+            None => return,
+            Some(m) => m,
+        };
+        let slots = menu
+            .slots
+            .get_or_insert_with(|| list_slots(&project))
+            .clone();
+        egui::Window::new("Menu").show(egui_context.ctx(), |ui| {
+            ui.horizontal(|ui| {
+                ui.label("New slot:");
+                ui.text_edit_singleline(&mut menu.slot);
+                if ui.button("Save").clicked() {
+                    let slot = menu.slot.clone();
+                    menu.info = match write_slot(&project, &slot, &player.save_game(&game_state)) {
+                        Ok(()) => format!("Saved to {}", slot),
+                        Err(e) => {
+                            results.send(PersistenceResult::Failed(e));
+                            format!("Could not save to {}", slot)
+                        }
+                    };
+                    menu.slots = None;
+                }
+            });
+            ui.separator();
+            for (slot, _) in slots.iter() {
+                ui.horizontal(|ui| {
+                    ui.label(slot);
+                    if ui.small_button("load").clicked() {
+                        match read_slot(&project, slot) {
+                            Ok(save) => {
+                                player.load_game(save, &mut game_state, &mut player_state);
+                                commands.remove_resource::<Self>();
+                            }
+                            Err(e) => {
+                                results.send(PersistenceResult::Failed(e));
+                                menu.info = format!("Could not load {}", slot);
+                            }
+                        }
+                    }
+                    if ui.small_button("overwrite").clicked() {
+                        menu.info = match write_slot(&project, slot, &player.save_game(&game_state)) {
+                            Ok(()) => format!("Saved to {}", slot),
+                            Err(e) => {
+                                results.send(PersistenceResult::Failed(e));
+                                format!("Could not save to {}", slot)
+                            }
+                        };
+                        menu.slots = None;
+                    }
+                });
+            }
+            ui.separator();
+            ui.label(&menu.info);
+            ui.label("Esc: close, F5: quicksave, F9: quickload");
+            ui.separator();
+            player.settings_ui(ui);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_state::Value;
+
+    fn save_at(slide: &str) -> SaveGame {
+        let mut state = GameState::default();
+        state.variables.insert("gold".into(), Value::Int(3));
+        SaveGame {
+            current_slide: slide.into(),
+            history: vec![HistoryEntry {
+                slide: "start".into(),
+                action: "Go on".into(),
+                state: Some(GameState::default()),
+            }],
+            state,
+        }
+    }
+
+    #[test]
+    fn writes_and_reads_slots() {
+        let project = Project::temp("slots");
+        assert_eq!(list_slots(&project), vec![]);
+        write_slot(&project, "my save", &save_at("cave")).unwrap();
+        assert_eq!(read_slot(&project, "my save"), Ok(save_at("cave")));
+        write_slot(&project, "my save", &save_at("lake")).unwrap();
+        assert_eq!(read_slot(&project, "my save"), Ok(save_at("lake")));
+        assert!(read_slot(&project, "missing").is_err());
+        fs::remove_dir_all(&project.dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_bad_slot_names() {
+        let project = Project::temp("bad_slots");
+        assert!(write_slot(&project, " ", &save_at("cave")).is_err());
+        assert!(write_slot(&project, "../outside", &save_at("cave")).is_err());
+        assert_eq!(list_slots(&project), vec![]);
+        fs::remove_dir_all(&project.dir).unwrap();
+    }
+
+    #[test]
+    fn lists_latest_slots_first() {
+        let project = Project::temp("list_slots");
+        write_slot(&project, "older", &save_at("cave")).unwrap();
+        write_slot(&project, "newer", &save_at("lake")).unwrap();
+        // Only the saves themselves are slots.
+        fs::write(project.path(SAVES_DIR).join("notes.txt"), "").unwrap();
+        let a_minute_ago = SystemTime::now() - std::time::Duration::from_secs(60);
+        filetime::set_file_mtime(
+            slot_path(&project, "older"),
+            filetime::FileTime::from_system_time(a_minute_ago),
+        )
+        .unwrap();
+        let slots: Vec<_> = list_slots(&project).into_iter().map(|(slot, _)| slot).collect();
+        assert_eq!(slots, vec!["newer".to_string(), "older".to_string()]);
+        fs::remove_dir_all(&project.dir).unwrap();
+    }
+}
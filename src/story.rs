@@ -0,0 +1,365 @@
+use crate::{
+    game_state::GameState,
+    images::Background,
+    items::Item,
+    lint::{lint, Problem},
+    manifest::StoryManifest,
+    model::{Action, Crudable, Slide},
+    persistence::{read_resources, save_resources, Persistable, PersistenceError, SCHEMA_VERSION},
+    project::Project,
+    saves::HistoryEntry,
+};
+
+/// The whole story as plain data. In the app it lives split into entities and
+/// resources, this is the shape for everything that runs without Bevy.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Story {
+    pub slides: Vec<Slide>,
+    pub backgrounds: Vec<Background>,
+    pub items: Vec<Item>,
+    pub manifest: StoryManifest,
+}
+
+impl Story {
+    /// Reads every story file of the project, missing ones are empty.
+    pub fn load(project: &Project) -> Result<Self, PersistenceError> {
+        Ok(Self {
+            slides: Self::load_part(project)?.0,
+            backgrounds: Self::load_part(project)?.0,
+            items: Self::load_part(project)?.0,
+            manifest: StoryManifest::load(project)?,
+        })
+    }
+
+    pub fn save(&self, project: &Project) -> Result<(), PersistenceError> {
+        Self::save_part(project, self.slides.clone())?;
+        Self::save_part(project, self.backgrounds.clone())?;
+        Self::save_part(project, self.items.clone())?;
+        self.manifest.save(project)
+    }
+
+    /// Reads the resources of one kind, for the app which keeps every kind
+    /// in entities of its own. A missing file is empty. Gives them along with
+    /// the version the file had.
+    pub fn load_part<R: Persistable>(project: &Project) -> Result<(Vec<R>, u32), PersistenceError> {
+        let path = project.path(R::file_name());
+        if !path.exists() {
+            return Ok((vec![], SCHEMA_VERSION));
+        }
+        read_resources(&path)
+    }
+
+    /// Writes the resources of one kind, gives how many were written.
+    pub fn save_part<R: Persistable>(
+        project: &Project,
+        resources: Vec<R>,
+    ) -> Result<usize, PersistenceError> {
+        save_resources(project, resources)
+    }
+
+    pub fn validate(&self) -> Vec<Problem> {
+        lint(&self.slides, &self.backgrounds, &self.manifest)
+    }
+
+    pub fn start(&self) -> &str {
+        &self.manifest.start_slide
+    }
+
+    pub fn slide(&self, id: &str) -> Option<&Slide> {
+        self.slides.iter().find(|s| s.id == id)
+    }
+
+    /// Name of the slide with the id, or the id itself when it is missing.
+    pub fn slide_name<'a>(&'a self, id: &'a str) -> &'a str {
+        self.slide(id).map_or(id, |s| s.name.as_str())
+    }
+
+    pub fn background_of(&self, slide: &Slide) -> Option<&Background> {
+        self.backgrounds
+            .iter()
+            .find(|b| b.id() == slide.background)
+    }
+}
+
+/// Where a reader is in a story and how they got there, whatever shows it.
+/// Slides are kept by id, so renaming them does not lose the reader.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Progress {
+    pub current_slide: String,
+    /// Slides left behind so far and the action taken on each of them.
+    pub history: Vec<HistoryEntry>,
+    /// Last slide whose effects were applied, so they run once per visit.
+    pub entered_slide: Option<String>,
+}
+
+impl Progress {
+    pub fn starting_at(slide: &str) -> Self {
+        Self {
+            current_slide: slide.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Applies the effects of entering the slide once per visit, gives
+    /// whether it was entered just now.
+    pub fn enter(&mut self, slide: &Slide, game_state: &mut GameState) -> bool {
+        if self.entered_slide.as_ref() == Some(&slide.id) {
+            return false;
+        }
+        game_state.apply_all(&slide.effects);
+        self.entered_slide = Some(slide.id.clone());
+        true
+    }
+
+    /// Records the choice made on the current slide and applies its effects,
+    /// gives the slide the action leads to. Moving there is up to the caller,
+    /// which may want to show the way out of the current one first.
+    pub fn choose(&mut self, action: &Action, game_state: &mut GameState) -> String {
+        self.history.push(HistoryEntry {
+            slide: self.current_slide.clone(),
+            action: action.text.clone(),
+            state: Some(game_state.clone()),
+        });
+        game_state.apply_all(&action.effects);
+        action.target_slide.clone()
+    }
+
+    /// Moves on to the slide. Its effects apply on the next `enter` even when
+    /// it is the slide being left, as an action leading back to its own slide
+    /// enters it again.
+    pub fn go_to(&mut self, slide: String) {
+        self.current_slide = slide;
+        self.entered_slide = None;
+    }
+
+    /// Whether the last choice can be undone, old saves did not keep the
+    /// state needed for it.
+    pub fn can_step_back(&self) -> bool {
+        matches!(self.history.last(), Some(HistoryEntry { state: Some(_), .. }))
+    }
+
+    /// Goes back to the slide of the last choice, as it was before choosing.
+    pub fn step_back(&mut self, game_state: &mut GameState) -> bool {
+        if !self.can_step_back() {
+            return false;
+        }
+        let entry = self.history.pop().unwrap();
+        *game_state = entry.state.unwrap();
+        // The state was taken after the effects of entering the slide.
+        self.entered_slide = Some(entry.slide.clone());
+        self.current_slide = entry.slide;
+        true
+    }
+
+    pub fn has_seen(&self, slide: &str) -> bool {
+        self.history.iter().any(|h| h.slide == slide)
+    }
+}
+
+/// Stories to test with, for the tests of every module.
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use crate::{
+        game_state::Effect,
+        model::{Action, Slide},
+    };
+
+    /// A slide named after its id in capitals.
+    pub(crate) fn slide(id: &str, actions: Vec<Action>) -> Slide {
+        Slide {
+            id: id.into(),
+            name: id.to_uppercase(),
+            actions,
+            ..Slide::new(String::new())
+        }
+    }
+
+    pub(crate) fn action(text: &str, target_slide: &str, effects: Vec<Effect>) -> Action {
+        Action {
+            text: text.into(),
+            target_slide: target_slide.into(),
+            effects,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn increment(var: &str) -> Effect {
+        Effect::Increment {
+            var: var.into(),
+            by: 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fixtures::*, *};
+    use crate::{game_state::Value, lint::SlideRef};
+
+    #[test]
+    fn enter_applies_slide_effects_once_per_visit() {
+        let mut hall = slide("hall", vec![]);
+        hall.effects = vec![increment("visits")];
+        let mut state = GameState::default();
+        let mut progress = Progress::starting_at("hall");
+
+        assert!(progress.enter(&hall, &mut state));
+        assert!(!progress.enter(&hall, &mut state));
+        assert_eq!(state.get("visits"), Value::Int(1));
+    }
+
+    #[test]
+    fn entering_again_through_an_action_applies_slide_effects_again() {
+        let mut hall = slide("hall", vec![action("Wait", "hall", vec![])]);
+        hall.effects = vec![increment("visits")];
+        let mut state = GameState::default();
+        let mut progress = Progress::starting_at("hall");
+
+        progress.enter(&hall, &mut state);
+        let next = progress.choose(&hall.actions[0], &mut state);
+        // Until the caller moves on the slide is still being left.
+        assert!(!progress.enter(&hall, &mut state));
+        progress.go_to(next);
+        assert!(progress.enter(&hall, &mut state));
+        assert_eq!(state.get("visits"), Value::Int(2));
+    }
+
+    #[test]
+    fn choose_records_the_state_before_the_action() {
+        let go = action("Go", "cellar", vec![increment("steps")]);
+        let mut state = GameState::default();
+        let mut progress = Progress::starting_at("hall");
+
+        assert_eq!(progress.choose(&go, &mut state), "cellar");
+        assert_eq!(state.get("steps"), Value::Int(1));
+        assert_eq!(progress.current_slide, "hall");
+        assert_eq!(progress.history.len(), 1);
+        assert_eq!(progress.history[0].slide, "hall");
+        assert_eq!(progress.history[0].action, "Go");
+        assert_eq!(progress.history[0].state, Some(GameState::default()));
+        assert!(progress.has_seen("hall"));
+        assert!(!progress.has_seen("cellar"));
+    }
+
+    #[test]
+    fn step_back_undoes_the_last_choice() {
+        let mut hall = slide("hall", vec![]);
+        hall.effects = vec![increment("visits")];
+        let mut cellar = slide("cellar", vec![]);
+        cellar.effects = vec![increment("visits")];
+        let go = action("Go", "cellar", vec![increment("steps")]);
+        let mut state = GameState::default();
+        let mut progress = Progress::starting_at("hall");
+
+        progress.enter(&hall, &mut state);
+        let next = progress.choose(&go, &mut state);
+        progress.go_to(next);
+        progress.enter(&cellar, &mut state);
+        assert_eq!(state.get("visits"), Value::Int(2));
+
+        assert!(progress.step_back(&mut state));
+        assert_eq!(progress.current_slide, "hall");
+        assert_eq!(state.get("visits"), Value::Int(1));
+        assert_eq!(state.get("steps"), Value::Bool(false));
+        // The effects of entering it are already in the restored state.
+        assert!(!progress.enter(&hall, &mut state));
+        assert!(!progress.step_back(&mut state));
+    }
+
+    #[test]
+    fn step_back_needs_the_state_of_the_choice() {
+        let mut progress = Progress::starting_at("cellar");
+        progress.history.push(HistoryEntry {
+            slide: "hall".into(),
+            action: "Go".into(),
+            state: None,
+        });
+        assert!(!progress.can_step_back());
+        assert!(!progress.step_back(&mut GameState::default()));
+        assert_eq!(progress.current_slide, "cellar");
+    }
+
+    fn slide_ref(id: &str) -> SlideRef {
+        SlideRef {
+            id: id.into(),
+            name: id.to_uppercase(),
+        }
+    }
+
+    #[test]
+    fn validate_finds_problems() {
+        let mut story = Story {
+            slides: vec![
+                slide("hall", vec![action("Go", "cellar", vec![]), action("Up", "attic", vec![])]),
+                slide("cellar", vec![]),
+                slide("garden", vec![action("Back", "hall", vec![])]),
+            ],
+            ..Default::default()
+        };
+        story.slides[1].ending = true;
+        story.manifest.start_slide = "hall".into();
+        assert_eq!(
+            story.validate(),
+            vec![
+                Problem::BrokenLink {
+                    slide: slide_ref("hall"),
+                    action: "Up".into(),
+                    target: "attic".into()
+                },
+                Problem::Unreachable {
+                    slide: slide_ref("garden")
+                },
+            ]
+        );
+
+        story.slides[2].actions.clear();
+        story.manifest.start_slide = "attic".into();
+        assert_eq!(
+            story.validate(),
+            vec![
+                Problem::MissingStartSlide {
+                    start: "attic".into()
+                },
+                Problem::DeadEnd {
+                    slide: slide_ref("garden")
+                },
+                Problem::BrokenLink {
+                    slide: slide_ref("hall"),
+                    action: "Up".into(),
+                    target: "attic".into()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_finds_slides_sharing_a_name() {
+        let mut story = Story {
+            slides: vec![
+                slide("hall", vec![action("Go", "hall2", vec![])]),
+                slide("hall2", vec![]),
+            ],
+            ..Default::default()
+        };
+        story.slides[1].name = "HALL".into();
+        story.slides[1].ending = true;
+        story.manifest.start_slide = "hall".into();
+        let problems = story.validate();
+        assert_eq!(
+            problems,
+            vec![
+                Problem::DuplicateSlideName {
+                    slide: slide_ref("hall")
+                },
+                Problem::DuplicateSlideName {
+                    slide: SlideRef {
+                        id: "hall2".into(),
+                        name: "HALL".into()
+                    }
+                },
+            ]
+        );
+        assert_eq!(problems[1].slide(), Some("hall2"));
+        assert_eq!(problems[1].to_string(), "HALL: another slide has the same name");
+    }
+}
@@ -0,0 +1,511 @@
+use serde::*;
+use std::{fs, path::PathBuf};
+
+use crate::{
+    game_state::Effect,
+    model::{new_id, Action, Crudable, Slide},
+    persistence::{write_text, PersistenceError},
+    project::Project,
+    story::Story,
+};
+
+/// Where Twee In reads from and Twee Out writes to.
+pub fn file_path(project: &Project) -> PathBuf {
+    project.path("story.twee")
+}
+
+const BACKGROUND_TAG: &str = "bg:";
+const ENDING_TAG: &str = "ending";
+
+/// What Twee can not express goes into the passage metadata, which Twine
+/// keeps next to the position.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PassageMetadata {
+    /// "x,y" as Twine writes it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    position: Option<String>,
+    /// Only for names a tag can not hold.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    background: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    effects: Vec<Effect>,
+    /// One per link, in order, when any link has a condition or effects.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    actions: Vec<ActionMetadata>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ActionMetadata {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    condition: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    effects: Vec<Effect>,
+}
+
+/// Twee refers to passages and backgrounds by name, `import` turns those into
+/// ids.
+#[derive(Debug, Default)]
+pub struct TweeStory {
+    pub title: Option<String>,
+    pub start: Option<String>,
+    pub slides: Vec<Slide>,
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        if matches!(c, '\\' | '[' | ']' | '{' | '}') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Splits a passage header, everything after the `::`, into its name, tags
+/// and metadata.
+fn parse_header(header: &str) -> (String, Vec<String>, Option<&str>) {
+    let mut name = String::new();
+    let mut chars = header.trim().char_indices();
+    let mut rest = "";
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some((_, escaped)) = chars.next() {
+                    name.push(escaped);
+                }
+            }
+            '[' | '{' => {
+                rest = &header.trim()[i..];
+                break;
+            }
+            _ => name.push(c),
+        }
+    }
+    let mut tags = vec![];
+    if rest.starts_with('[') {
+        // An unclosed tag list runs to the end of the header.
+        let end = rest.find(']').unwrap_or(rest.len());
+        tags = rest[1..end]
+            .split_whitespace()
+            .map(|t| t.replace('\\', ""))
+            .collect();
+        rest = rest.get(end + 1..).unwrap_or("").trim_start();
+    }
+    let metadata = if rest.starts_with('{') { Some(rest) } else { None };
+    (name.trim().into(), tags, metadata)
+}
+
+/// Reads a `[[...]]` link into its text and target, in any of the forms
+/// Twine writes.
+fn parse_link(link: &str) -> (String, String) {
+    if let Some(i) = link.rfind("->") {
+        (link[..i].trim().into(), link[i + 2..].trim().into())
+    } else if let Some(i) = link.find("<-") {
+        (link[i + 2..].trim().into(), link[..i].trim().into())
+    } else if let Some(i) = link.rfind('|') {
+        (link[..i].trim().into(), link[i + 1..].trim().into())
+    } else {
+        (link.trim().into(), link.trim().into())
+    }
+}
+
+/// Takes the links out of the passage text, what is left is the description.
+fn split_links(text: &str) -> (String, Vec<Action>) {
+    let mut description = String::new();
+    let mut actions = vec![];
+    let mut rest = text;
+    while let Some(start) = rest.find("[[") {
+        let end = match rest[start..].find("]]") {
+            None => break,
+            Some(end) => start + end,
+        };
+        description += &rest[..start];
+        let (text, target_slide) = parse_link(&rest[start + 2..end]);
+        actions.push(Action {
+            text,
+            target_slide,
+            ..Default::default()
+        });
+        rest = &rest[end + 2..];
+    }
+    description += rest;
+    let description = description
+        .lines()
+        .map(|l| l.trim_end())
+        .collect::<Vec<_>>()
+        .join("\n");
+    (description.trim().into(), actions)
+}
+
+fn passage_to_slide(
+    name: String,
+    tags: &[String],
+    metadata: PassageMetadata,
+    text: &str,
+) -> Slide {
+    let (description, mut actions) = split_links(text);
+    if actions.len() == metadata.actions.len() {
+        for (a, m) in actions.iter_mut().zip(metadata.actions) {
+            a.condition = m.condition;
+            a.effects = m.effects;
+        }
+    }
+    let background = tags
+        .iter()
+        .find_map(|t| t.strip_prefix(BACKGROUND_TAG))
+        .map(String::from)
+        .or(metadata.background)
+        .unwrap_or_default();
+    let position = metadata.position.and_then(|p| {
+        let mut coords = p.split(',').map(|c| c.trim().parse::<f32>().ok());
+        Some((coords.next()?? as i32, coords.next()?? as i32))
+    });
+    Slide {
+        id: String::new(),
+        name,
+        description,
+        background,
+        actions,
+        effects: metadata.effects,
+        ending: tags.iter().any(|t| t == ENDING_TAG),
+        position,
+    }
+}
+
+pub fn parse_twee(source: &str) -> Result<TweeStory, String> {
+    let mut story = TweeStory::default();
+    // Header line number, header and text of every passage.
+    let mut passages: Vec<(usize, &str, String)> = vec![];
+    for (n, line) in source.lines().enumerate() {
+        if let Some(header) = line.strip_prefix("::") {
+            passages.push((n + 1, header, String::new()));
+            continue;
+        }
+        match passages.last_mut() {
+            // Anything before the first passage is ignored, as Twine does.
+            None => {}
+            Some((_, _, text)) => {
+                *text += line.strip_prefix('\\').filter(|l| l.starts_with("::")).unwrap_or(line);
+                *text += "\n";
+            }
+        }
+    }
+    for (line, header, text) in passages {
+        let (name, tags, metadata) = parse_header(header);
+        if name.is_empty() {
+            return Err(format!("line {}: passage without a name", line));
+        }
+        match name.as_str() {
+            "StoryTitle" => story.title = Some(text.trim().into()),
+            "StoryData" => {
+                let data: serde_json::Value = serde_json::from_str(&text)
+                    .map_err(|e| format!("line {}: invalid StoryData: {}", line, e))?;
+                story.start = data
+                    .get("start")
+                    .and_then(|s| s.as_str())
+                    .map(String::from);
+            }
+            _ if tags.iter().any(|t| t == "script" || t == "stylesheet") => {}
+            _ => {
+                let metadata: PassageMetadata = match metadata {
+                    None => PassageMetadata::default(),
+                    Some(m) => serde_json::from_str(m)
+                        .map_err(|e| format!("line {}: invalid passage metadata: {}", line, e))?,
+                };
+                story.slides.push(passage_to_slide(name, &tags, metadata, &text));
+            }
+        }
+    }
+    Ok(story)
+}
+
+/// Twine wants an IFID to tell stories apart, this one stays the same for as
+/// long as the title does.
+fn ifid(title: &str) -> String {
+    let hash = |text: &str| {
+        text.bytes().fold(0xcbf29ce484222325u64, |hash, b| {
+            (hash ^ b as u64).wrapping_mul(0x100000001b3)
+        })
+    };
+    let (a, b) = (hash(title), hash(&format!("{}ifid", title)));
+    format!(
+        "{:08X}-{:04X}-4{:03X}-{:04X}-{:012X}",
+        a >> 32,
+        (a >> 16) & 0xFFFF,
+        a & 0xFFF,
+        (b >> 48) & 0x3FFF | 0x8000,
+        b & 0xFFFF_FFFF_FFFF
+    )
+}
+
+/// Passages already in the story keep their slide's id, new ones get their
+/// own. Links and backgrounds that match nothing are left as they are written,
+/// for the problems window to point out.
+fn resolve_names(twee: &mut TweeStory, story: &Story) {
+    for s in twee.slides.iter_mut() {
+        s.id = story
+            .slides
+            .iter()
+            .find(|old| old.name == s.name)
+            .map_or_else(|| new_id("slide"), |old| old.id.clone());
+    }
+    let id_of = |name: &str| {
+        twee.slides
+            .iter()
+            .chain(story.slides.iter())
+            .find(|s| s.name == name)
+            .map(|s| s.id.clone())
+    };
+    let targets: Vec<Vec<Option<String>>> = twee
+        .slides
+        .iter()
+        .map(|s| s.actions.iter().map(|a| id_of(&a.target_slide)).collect())
+        .collect();
+    let start = twee.start.as_deref().map(|start| id_of(start).unwrap_or(start.into()));
+    for (s, targets) in twee.slides.iter_mut().zip(targets) {
+        for (a, target) in s.actions.iter_mut().zip(targets) {
+            if let Some(target) = target {
+                a.target_slide = target;
+            }
+        }
+        if let Some(bg) = story.backgrounds.iter().find(|b| b.name() == s.background) {
+            s.background = bg.id();
+        }
+    }
+    twee.start = start;
+}
+
+/// Writes the action as a link `split_links` reads back the same, or tells
+/// why it can not be written.
+fn action_to_link(text: &str, target: &str) -> Result<String, String> {
+    if text.contains("]]") || target.contains("]]") {
+        return Err(format!("\"{}\" to \"{}\": links can not have \"]]\" in them", text, target));
+    }
+    // The last `->` of a link is taken as the arrow and the first `]]` as its
+    // end, the text may have either but not the target.
+    if target.contains("->") || target.ends_with(']') {
+        return Err(format!("\"{}\" can not be the target of a link", target));
+    }
+    if text == target && !["->", "<-", "|"].iter().any(|s| text.contains(s)) {
+        Ok(format!("[[{}]]", text))
+    } else {
+        Ok(format!("[[{}->{}]]", text, target))
+    }
+}
+
+fn slide_to_passage(story: &Story, slide: &Slide) -> Result<String, String> {
+    let mut tags = vec![];
+    let mut metadata = PassageMetadata {
+        position: slide.position.map(|(x, y)| format!("{},{}", x, y)),
+        effects: slide.effects.clone(),
+        ..Default::default()
+    };
+    let background = story
+        .background_of(slide)
+        .map_or(slide.background.clone(), |bg| bg.name());
+    if background.contains(char::is_whitespace) {
+        metadata.background = Some(background);
+    } else if !background.is_empty() {
+        tags.push(escape(&format!("{}{}", BACKGROUND_TAG, background)));
+    }
+    if slide.ending {
+        tags.push(ENDING_TAG.into());
+    }
+    if slide
+        .actions
+        .iter()
+        .any(|a| !a.condition.is_empty() || !a.effects.is_empty())
+    {
+        metadata.actions = slide
+            .actions
+            .iter()
+            .map(|a| ActionMetadata {
+                condition: a.condition.clone(),
+                effects: a.effects.clone(),
+            })
+            .collect();
+    }
+
+    let mut passage = format!(":: {}", escape(&slide.name));
+    if !tags.is_empty() {
+        passage += &format!(" [{}]", tags.join(" "));
+    }
+    let metadata = serde_json::to_string(&metadata).expect("Metadata is always valid JSON");
+    if metadata != "{}" {
+        passage += " ";
+        passage += &metadata;
+    }
+    passage += "\n";
+    for line in slide.description.lines() {
+        if line.starts_with("::") {
+            passage += "\\";
+        }
+        passage += line;
+        passage += "\n";
+    }
+    if !slide.actions.is_empty() {
+        passage += "\n";
+    }
+    for a in slide.actions.iter() {
+        let link = action_to_link(&a.text, story.slide_name(&a.target_slide))
+            .map_err(|e| format!("slide {}: {}", slide.name, e))?;
+        passage += &link;
+        passage += "\n";
+    }
+    Ok(passage)
+}
+
+/// Fails on links Twee can not hold, naming the slide they are on.
+pub fn to_twee(story: &Story) -> Result<String, String> {
+    let data = serde_json::json!({
+        "ifid": ifid(&story.manifest.title),
+        "format": "Harlowe",
+        "format-version": "3.2.3",
+        "start": story.slide_name(story.start()),
+    });
+    let mut twee = format!(
+        ":: StoryTitle\n{}\n\n:: StoryData\n{}\n",
+        story.manifest.title,
+        serde_json::to_string_pretty(&data).expect("StoryData is always valid JSON")
+    );
+    let mut slides: Vec<&Slide> = story.slides.iter().collect();
+    slides.sort_by(|a, b| a.name.cmp(&b.name));
+    for s in slides {
+        twee += "\n";
+        twee += &slide_to_passage(story, s)?;
+    }
+    Ok(twee)
+}
+
+/// Reads the Twee file, referring to slides and backgrounds of the story by
+/// their ids.
+pub fn import(project: &Project, story: &Story) -> Result<TweeStory, PersistenceError> {
+    let path = file_path(project);
+    let source = fs::read_to_string(&path).map_err(|e| PersistenceError::io(&path, e))?;
+    let mut twee = parse_twee(&source).map_err(|message| PersistenceError {
+        path: path.clone(),
+        message,
+        position: None,
+    })?;
+    resolve_names(&mut twee, story);
+    Ok(twee)
+}
+
+pub fn export(project: &Project, story: &Story) -> Result<(), PersistenceError> {
+    let path = file_path(project);
+    let twee = to_twee(story).map_err(|message| PersistenceError {
+        path: path.clone(),
+        message,
+        position: None,
+    })?;
+    write_text(&path, &twee)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        game_state::parse_condition,
+        story::fixtures::{action, increment, slide},
+    };
+
+    fn passage(id: &str, name: &str, description: &str, actions: Vec<Action>) -> Slide {
+        Slide {
+            name: name.into(),
+            description: description.into(),
+            ..slide(id, actions)
+        }
+    }
+
+    fn story_of(slides: Vec<Slide>) -> Story {
+        let mut story = Story {
+            slides,
+            ..Default::default()
+        };
+        story.manifest.title = "A [test] story".into();
+        story.manifest.start_slide = "s1".into();
+        story
+    }
+
+    fn round_trip(story: &Story) -> TweeStory {
+        let mut twee = parse_twee(&to_twee(story).unwrap()).unwrap();
+        resolve_names(&mut twee, story);
+        twee
+    }
+
+    fn sorted(mut slides: Vec<Slide>) -> Vec<Slide> {
+        slides.sort_by(|a, b| a.name.cmp(&b.name));
+        slides
+    }
+
+    #[test]
+    fn round_trips_slides() {
+        let mut start = passage(
+            "s1",
+            "The {start}",
+            "First line.\n:: not a header\n\nLast line.",
+            vec![
+                action("Hall [west] wing", "s2", vec![]),
+                action("A -> B | C <- D", "s2", vec![]),
+                action("[[nested", "s1", vec![]),
+            ],
+        );
+        start.position = Some((10, -20));
+        start.effects = vec![increment("visits")];
+        start.actions[1].condition = "has(key) && visits > 1".into();
+        start.actions[1].effects = vec![Effect::Give { item: "lamp".into() }];
+        let mut hall = passage("s2", "Hall [west] wing", "", vec![action("Back", "s1", vec![])]);
+        hall.ending = true;
+        let story = story_of(vec![start, hall]);
+
+        let twee = round_trip(&story);
+        assert_eq!(twee.title.as_deref(), Some("A [test] story"));
+        assert_eq!(twee.start.as_deref(), Some("s1"));
+        let imported = twee.slides.iter().find(|s| s.id == "s1").unwrap();
+        assert!(parse_condition(&imported.actions[1].condition).is_ok());
+        assert_eq!(sorted(twee.slides), sorted(story.slides));
+    }
+
+    #[test]
+    fn round_trips_names_with_link_separators() {
+        let story = story_of(vec![
+            passage(
+                "s1",
+                "Start",
+                "",
+                vec![action("Go", "s2", vec![]), action("a|b", "s3", vec![])],
+            ),
+            passage("s2", "Left <- Right", "", vec![action("Left <- Right", "s2", vec![])]),
+            passage("s3", "a|b", "", vec![]),
+        ]);
+        assert_eq!(sorted(round_trip(&story).slides), sorted(story.slides));
+    }
+
+    #[test]
+    fn rejects_links_twee_can_not_hold() {
+        let story = story_of(vec![
+            passage("s1", "Start", "", vec![action("Jump]]", "s1", vec![])]),
+        ]);
+        assert!(to_twee(&story).unwrap_err().contains("Start"));
+
+        let story = story_of(vec![
+            passage("s1", "Start", "", vec![action("Go", "s2", vec![])]),
+            passage("s2", "There -> here", "", vec![]),
+        ]);
+        assert!(to_twee(&story).is_err());
+    }
+
+    #[test]
+    fn reads_unclosed_tags() {
+        assert_eq!(parse_header(" Foo ["), ("Foo".into(), vec![], None));
+        assert_eq!(
+            parse_header(" Foo [ending bg:sky"),
+            ("Foo".into(), vec!["ending".into(), "bg:sky".into()], None)
+        );
+        assert_eq!(
+            parse_header(" Foo [ending] {\"position\":\"1,2\"}"),
+            ("Foo".into(), vec!["ending".into()], Some("{\"position\":\"1,2\"}"))
+        );
+    }
+}
@@ -0,0 +1,337 @@
+use std::{
+    any::Any,
+    collections::{HashMap, VecDeque},
+    marker::PhantomData,
+};
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+
+use crate::{
+    model::{CrudEvent, Crudable, EditorsOpen},
+    persistence::{Persistable, PersistenceEvent},
+};
+
+/// Updates to the same resource closer together than this are one step, so a
+/// sentence typed into a slide is undone at once and not letter by letter.
+const COALESCE_SECONDS: f64 = 1.0;
+const MAX_STEPS: usize = 200;
+
+/// Undo/redo stack shared by every resource type, so Ctrl+Z always undoes the
+/// latest edit no matter what it was made to.
+#[derive(Default)]
+pub struct EditHistory {
+    undo: Vec<Step>,
+    redo: Vec<Step>,
+}
+
+struct Step {
+    kind: &'static str,
+    label: String,
+    /// Resource id and time of an update later updates may be merged into.
+    coalesce: Option<(String, f64)>,
+    /// An `Edit<R>` for the `R` whose `default_name_prefix` is `kind`.
+    edit: Box<dyn Any + Send + Sync>,
+}
+
+struct Edit<R> {
+    forward: Vec<CrudEvent<R>>,
+    inverse: Vec<CrudEvent<R>>,
+}
+
+/// Undo or redo the top step, which must be about resources of the given kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HistoryCommand {
+    Undo(&'static str),
+    Redo(&'static str),
+}
+
+impl EditHistory {
+    fn undo_command(&self) -> Option<HistoryCommand> {
+        self.undo.last().map(|s| HistoryCommand::Undo(s.kind))
+    }
+    fn redo_command(&self) -> Option<HistoryCommand> {
+        self.redo.last().map(|s| HistoryCommand::Redo(s.kind))
+    }
+}
+
+pub struct HistoryPlugin;
+
+impl Plugin for HistoryPlugin {
+    fn build(&self, builder: &mut AppBuilder) {
+        builder
+            .insert_resource(EditHistory::default())
+            .add_event::<HistoryCommand>()
+            .add_system(handle_keys.system())
+            .add_system(history_window.system());
+    }
+}
+
+fn handle_keys(
+    keys: Res<Input<KeyCode>>,
+    history: Res<EditHistory>,
+    mut history_commands: EventWriter<HistoryCommand>,
+    editors_open: Res<EditorsOpen>,
+    egui_context: ResMut<EguiContext>,
+) {
+    if !editors_open.0 || !keys.just_pressed(KeyCode::Z) {
+        return;
+    }
+    // Text fields undo their own typing.
+    if egui_context.ctx().wants_keyboard_input() {
+        return;
+    }
+    let ctrl = keys.pressed(KeyCode::LControl) || keys.pressed(KeyCode::RControl);
+    let shift = keys.pressed(KeyCode::LShift) || keys.pressed(KeyCode::RShift);
+    if !ctrl {
+        return;
+    }
+    let command = if shift {
+        history.redo_command()
+    } else {
+        history.undo_command()
+    };
+    if let Some(command) = command {
+        history_commands.send(command);
+    }
+}
+
+fn history_window(
+    egui_context: ResMut<EguiContext>,
+    history: Res<EditHistory>,
+    mut history_commands: EventWriter<HistoryCommand>,
+    editors_open: Res<EditorsOpen>,
+) {
+    if !editors_open.0 {
+        return;
+    }
+    egui::Window::new("Edit history").show(egui_context.ctx(), |ui| {
+        ui.horizontal(|ui| {
+            if let Some(command) = history.undo_command() {
+                if ui.button("Undo").clicked() {
+                    history_commands.send(command);
+                }
+            }
+            if let Some(command) = history.redo_command() {
+                if ui.button("Redo").clicked() {
+                    history_commands.send(command);
+                }
+            }
+        });
+        ui.label("Ctrl+Z: undo, Ctrl+Shift+Z: redo");
+        ui.separator();
+        egui::ScrollArea::auto_sized().show(ui, |ui| {
+            for s in history.redo.iter() {
+                ui.colored_label(egui::Color32::GRAY, &s.label);
+            }
+            for s in history.undo.iter().rev() {
+                ui.label(&s.label);
+            }
+        });
+    });
+}
+
+/// Records every `CrudEvent<R>` with its inverse into the `EditHistory`.
+pub struct UndoPlugin<R> {
+    _phantom: PhantomData<R>,
+}
+
+/// Events that must not be recorded: the ones undo and redo send themselves,
+/// and the ones merging a reloaded file.
+pub(crate) struct Replaying<R>(VecDeque<CrudEvent<R>>);
+
+impl<R: Clone> Replaying<R> {
+    /// Leaves an event about to be sent out of the history, like the ones
+    /// merging a reloaded file into the world.
+    pub(crate) fn skip(&mut self, e: &CrudEvent<R>) {
+        self.0.push_back(e.clone());
+    }
+}
+
+impl<R: 'static + Crudable + Persistable + PartialEq> Plugin for UndoPlugin<R> {
+    fn build(&self, builder: &mut AppBuilder) {
+        builder
+            .insert_resource(Replaying::<R>(VecDeque::new()))
+            .add_system(Self::record.system().before("crud"))
+            .add_system(Self::apply_commands.system())
+            .add_system(Self::forget_on_file_in.system());
+    }
+}
+
+impl<R: 'static + Crudable + Persistable + PartialEq> UndoPlugin<R> {
+    pub fn new() -> Self {
+        Self {
+            _phantom: Default::default(),
+        }
+    }
+
+    /// Runs before the CRUD handler, so the query still has the values the
+    /// events are about to overwrite.
+    fn record(
+        mut events: EventReader<CrudEvent<R>>,
+        resources: Query<&R>,
+        mut history: ResMut<EditHistory>,
+        mut replaying: ResMut<Replaying<R>>,
+        time: Res<Time>,
+    ) {
+        let kind = R::default_name_prefix();
+        let now = time.seconds_since_startup();
+        // Changes made by earlier events of this same batch, by id.
+        let mut shadow: HashMap<String, Option<R>> = HashMap::new();
+        let lookup = |shadow: &HashMap<String, Option<R>>, id: &str| -> Option<R> {
+            match shadow.get(id) {
+                Some(r) => r.clone(),
+                None => resources.iter().find(|r| r.id() == id).cloned(),
+            }
+        };
+
+        for e in events.iter() {
+            let replayed = replaying.0.front() == Some(e);
+            if replayed {
+                replaying.0.pop_front();
+            }
+            let (inverse, label) = match e {
+                CrudEvent::Created(res) => {
+                    shadow.insert(res.id(), Some(res.clone()));
+                    (
+                        CrudEvent::Deleted(res.id()),
+                        format!("Create {} {}", kind, res.name()),
+                    )
+                }
+                CrudEvent::Updated(res) => {
+                    let before = lookup(&shadow, &res.id());
+                    shadow.insert(res.id(), Some(res.clone()));
+                    match before {
+                        Some(before) if before != *res => (
+                            CrudEvent::Updated(before),
+                            format!("Edit {} {}", kind, res.name()),
+                        ),
+                        _ => continue,
+                    }
+                }
+                CrudEvent::Renamed(id, new_name) => {
+                    let before = match lookup(&shadow, id) {
+                        Some(before) => before,
+                        None => continue,
+                    };
+                    let mut renamed = before.clone();
+                    renamed.set_name(new_name.clone());
+                    // Resources going by their name get a new id with it.
+                    shadow.insert(id.clone(), None);
+                    shadow.insert(renamed.id(), Some(renamed.clone()));
+                    (
+                        CrudEvent::Renamed(renamed.id(), before.name()),
+                        format!("Rename {} {} to {}", kind, before.name(), new_name),
+                    )
+                }
+                CrudEvent::Deleted(id) => {
+                    let before = lookup(&shadow, id);
+                    shadow.insert(id.clone(), None);
+                    match before {
+                        Some(before) => {
+                            let label = format!("Delete {} {}", kind, before.name());
+                            (CrudEvent::Created(before), label)
+                        }
+                        None => continue,
+                    }
+                }
+            };
+            if replayed {
+                continue;
+            }
+
+            // A new edit, even one joining the last step, leaves nothing to redo.
+            history.redo.clear();
+            let coalesce = match e {
+                CrudEvent::Updated(res) => Some((res.id(), now)),
+                _ => None,
+            };
+            if let (Some((id, _)), Some(top)) = (&coalesce, history.undo.last_mut()) {
+                let joins = top.kind == kind
+                    && matches!(&top.coalesce, Some((i, t)) if i == id && now - t < COALESCE_SECONDS);
+                if joins {
+                    if let Some(edit) = top.edit.downcast_mut::<Edit<R>>() {
+                        edit.forward = vec![e.clone()];
+                        top.coalesce = coalesce;
+                        continue;
+                    }
+                }
+            }
+
+            history.undo.push(Step {
+                kind,
+                label,
+                coalesce,
+                edit: Box::new(Edit {
+                    forward: vec![e.clone()],
+                    inverse: vec![inverse],
+                }),
+            });
+            if history.undo.len() > MAX_STEPS {
+                history.undo.remove(0);
+            }
+        }
+    }
+
+    fn apply_commands(
+        mut history_commands: EventReader<HistoryCommand>,
+        mut history: ResMut<EditHistory>,
+        mut replaying: ResMut<Replaying<R>>,
+        mut crud_events: EventWriter<CrudEvent<R>>,
+    ) {
+        let kind = R::default_name_prefix();
+        for c in history_commands.iter() {
+            let (from_undo, step) = match *c {
+                HistoryCommand::Undo(k) if k == kind && history.undo_command() == Some(*c) => {
+                    (true, history.undo.pop().unwrap())
+                }
+                HistoryCommand::Redo(k) if k == kind && history.redo_command() == Some(*c) => {
+                    (false, history.redo.pop().unwrap())
+                }
+                _ => continue,
+            };
+            if let Some(edit) = step.edit.downcast_ref::<Edit<R>>() {
+                let events = if from_undo {
+                    &edit.inverse
+                } else {
+                    &edit.forward
+                };
+                for e in events.iter() {
+                    replaying.0.push_back(e.clone());
+                    crud_events.send(e.clone());
+                }
+            }
+            let step = Step {
+                coalesce: None,
+                ..step
+            };
+            if from_undo {
+                history.redo.push(step);
+            } else {
+                history.undo.push(step);
+            }
+        }
+    }
+
+    /// Steps recorded against the old contents make no sense after a File In,
+    /// and undoing a reload would quietly revert the file at the next save.
+    fn forget_on_file_in(
+        mut persistence: EventReader<PersistenceEvent<R>>,
+        mut history: ResMut<EditHistory>,
+        mut replaying: ResMut<Replaying<R>>,
+    ) {
+        let kind = R::default_name_prefix();
+        for e in persistence.iter() {
+            match e {
+                PersistenceEvent::FileIn | PersistenceEvent::Restore(_) => {
+                    replaying.0.clear();
+                }
+                // The merge is left out of the history through `Replaying`.
+                PersistenceEvent::Reload => {}
+                _ => continue,
+            }
+            history.undo.retain(|s| s.kind != kind);
+            history.redo.retain(|s| s.kind != kind);
+        }
+    }
+}
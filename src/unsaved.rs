@@ -0,0 +1,249 @@
+use std::{collections::HashSet, path::PathBuf};
+
+use bevy::{
+    app::{AppExit, Events},
+    prelude::*,
+    window::WindowCloseRequested,
+};
+use bevy_egui::{egui, EguiContext};
+
+use crate::{
+    images::Background,
+    items::Item,
+    manifest::StoryManifest,
+    model::Slide,
+    persistence::{PersistenceEvent, PersistenceResult},
+    project::{OpenProject, Project},
+};
+
+const AUTOSAVE_SECONDS: f32 = 120.;
+
+/// Files whose contents in the world differ from what was last filed in or
+/// out, by file name.
+#[derive(Debug, Default)]
+pub struct UnsavedChanges(HashSet<&'static str>);
+
+impl UnsavedChanges {
+    pub fn any(&self) -> bool {
+        !self.0.is_empty()
+    }
+
+    pub fn contains(&self, file_name: &str) -> bool {
+        self.0.contains(file_name)
+    }
+
+    pub fn set(&mut self, file_name: &'static str, unsaved: bool) {
+        if unsaved {
+            self.0.insert(file_name);
+        } else {
+            self.0.remove(file_name);
+        }
+    }
+
+    /// Goes after a title, like editors usually show unsaved files.
+    pub fn marker(unsaved: bool) -> &'static str {
+        if unsaved {
+            "*"
+        } else {
+            ""
+        }
+    }
+
+    fn files(&self) -> Vec<&'static str> {
+        let mut files: Vec<_> = self.0.iter().copied().collect();
+        files.sort();
+        files
+    }
+}
+
+/// Periodically writes files with unsaved changes to a sidecar next to them,
+/// leaving the files themselves and their backups alone.
+pub struct Autosave {
+    pub enabled: bool,
+    timer: Timer,
+}
+
+impl Default for Autosave {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            timer: Timer::from_seconds(AUTOSAVE_SECONDS, true),
+        }
+    }
+}
+
+impl Autosave {
+    /// Whether to autosave this frame, for systems labelled after "autosave".
+    pub fn due(&self) -> bool {
+        self.enabled && self.timer.just_finished()
+    }
+
+    pub fn sidecar_path(project: &Project, file_name: &str) -> PathBuf {
+        project.path(&format!("{}.autosave", file_name))
+    }
+
+    pub fn interval_minutes() -> u32 {
+        (AUTOSAVE_SECONDS / 60.) as u32
+    }
+
+    fn tick(time: Res<Time>, mut autosave: ResMut<Self>) {
+        if autosave.enabled {
+            autosave.timer.tick(time.delta());
+        }
+    }
+}
+
+pub struct UnsavedPlugin;
+
+impl Plugin for UnsavedPlugin {
+    fn build(&self, builder: &mut AppBuilder) {
+        builder
+            .insert_resource(UnsavedChanges::default())
+            .insert_resource(Autosave::default())
+            .add_system(Autosave::tick.system().label("autosave"))
+            .add_system_to_stage(CoreStage::PreUpdate, intercept_window_close.system())
+            .add_system(UnsavedPrompt::render.system());
+    }
+}
+
+/// Quits right away when everything is saved, asks first otherwise.
+pub fn quit(
+    unsaved: &UnsavedChanges,
+    commands: &mut Commands,
+    app_exit: &mut EventWriter<AppExit>,
+) {
+    if unsaved.any() {
+        commands.insert_resource(UnsavedPrompt::new(AfterPrompt::Quit));
+    } else {
+        app_exit.send(AppExit);
+    }
+}
+
+/// Opens the project right away when everything is saved, asks first
+/// otherwise.
+pub fn open_project(
+    dir: PathBuf,
+    unsaved: &UnsavedChanges,
+    commands: &mut Commands,
+    open_project: &mut EventWriter<OpenProject>,
+) {
+    if unsaved.any() {
+        commands.insert_resource(UnsavedPrompt::new(AfterPrompt::Open(dir)));
+    } else {
+        open_project.send(OpenProject(dir));
+    }
+}
+
+/// Bevy quits as soon as the window is asked to close, taking the requests
+/// away before it sees them lets unsaved changes be dealt with first.
+fn intercept_window_close(
+    mut close_requests: ResMut<Events<WindowCloseRequested>>,
+    unsaved: Res<UnsavedChanges>,
+    mut app_exit: EventWriter<AppExit>,
+    mut commands: Commands,
+) {
+    if close_requests.drain().count() > 0 {
+        quit(&unsaved, &mut commands, &mut app_exit);
+    }
+}
+
+/// What the prompt is in the way of, done once the changes are filed out or
+/// dropped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum AfterPrompt {
+    Quit,
+    Open(PathBuf),
+}
+
+#[derive(Debug)]
+struct UnsavedPrompt {
+    after: AfterPrompt,
+    /// A File Out was sent, go on once nothing is left unsaved.
+    saving: bool,
+    info: String,
+}
+
+impl UnsavedPrompt {
+    fn new(after: AfterPrompt) -> Self {
+        Self {
+            after,
+            saving: false,
+            info: String::new(),
+        }
+    }
+
+    fn proceed(
+        &self,
+        app_exit: &mut EventWriter<AppExit>,
+        open_project: &mut EventWriter<OpenProject>,
+        commands: &mut Commands,
+    ) {
+        match &self.after {
+            AfterPrompt::Quit => app_exit.send(AppExit),
+            AfterPrompt::Open(dir) => open_project.send(OpenProject(dir.clone())),
+        }
+        commands.remove_resource::<Self>();
+    }
+
+    fn render(
+        egui_context: ResMut<EguiContext>,
+        prompt: Option<ResMut<Self>>,
+        unsaved: Res<UnsavedChanges>,
+        mut results: EventReader<PersistenceResult>,
+        mut slide_persistence: EventWriter<PersistenceEvent<Slide>>,
+        mut bg_persistence: EventWriter<PersistenceEvent<Background>>,
+        mut item_persistence: EventWriter<PersistenceEvent<Item>>,
+        mut manifest_persistence: EventWriter<PersistenceEvent<StoryManifest>>,
+        mut app_exit: EventWriter<AppExit>,
+        mut open_project: EventWriter<OpenProject>,
+        mut commands: Commands,
+    ) {
+        // Read every frame, so only failures of our own File Out are seen.
+        let failed = results
+            .iter()
+            .any(|r| matches!(r, PersistenceResult::Failed(_)));
+        let mut prompt = match prompt {
+            None => return,
+            Some(p) => p,
+        };
+        if prompt.saving {
+            if failed {
+                prompt.saving = false;
+                prompt.info = "File Out failed, the changes are still unsaved.".into();
+            } else if !unsaved.any() {
+                prompt.proceed(&mut app_exit, &mut open_project, &mut commands);
+                return;
+            }
+        }
+        let (save_label, drop_label) = match prompt.after {
+            AfterPrompt::Quit => ("File Out and quit", "Quit without saving"),
+            AfterPrompt::Open(_) => ("File Out and open", "Open without saving"),
+        };
+        egui::Window::new("Unsaved changes").show(egui_context.ctx(), |ui| {
+            ui.label("These files have changes that were not filed out:");
+            for f in unsaved.files() {
+                ui.label(f);
+            }
+            ui.horizontal(|ui| {
+                if prompt.saving {
+                    ui.label("Saving...");
+                } else if ui.button(save_label).clicked() {
+                    slide_persistence.send(PersistenceEvent::FileOut);
+                    bg_persistence.send(PersistenceEvent::FileOut);
+                    item_persistence.send(PersistenceEvent::FileOut);
+                    manifest_persistence.send(PersistenceEvent::FileOut);
+                    prompt.saving = true;
+                }
+                if ui.button(drop_label).clicked() {
+                    prompt.proceed(&mut app_exit, &mut open_project, &mut commands);
+                }
+                if ui.button("Cancel").clicked() {
+                    commands.remove_resource::<Self>();
+                }
+            });
+            if !prompt.info.is_empty() {
+                ui.colored_label(egui::Color32::RED, &prompt.info);
+            }
+        });
+    }
+}
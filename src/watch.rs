@@ -0,0 +1,183 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+
+use crate::{
+    images::Background,
+    items::Item,
+    manifest::StoryManifest,
+    model::Slide,
+    persistence::{Persistable, PersistenceEvent},
+    project::Project,
+    unsaved::UnsavedChanges,
+};
+
+/// How often the story files are checked for changes made outside the editor.
+const POLL_SECONDS: f32 = 1.;
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// When each story file was last modified as far as the editor knows, kept
+/// up to date by its own File In and File Out so those are not taken for
+/// outside changes.
+#[derive(Debug, Default)]
+pub struct DiskTimes(HashMap<PathBuf, SystemTime>);
+
+impl DiskTimes {
+    pub fn record(&mut self, path: &Path) {
+        if let Some(m) = modified(path) {
+            self.0.insert(path.into(), m);
+        }
+    }
+}
+
+struct Watcher(Timer);
+
+pub struct WatchPlugin;
+
+impl Plugin for WatchPlugin {
+    fn build(&self, builder: &mut AppBuilder) {
+        builder
+            .insert_resource(DiskTimes::default())
+            .insert_resource(Watcher(Timer::from_seconds(POLL_SECONDS, true)))
+            .add_system(watch.system())
+            .add_system(ReloadConflict::render.system());
+    }
+}
+
+/// Sends `Reload` to whichever type is kept in the file.
+fn reload(
+    file: &str,
+    slide_persistence: &mut EventWriter<PersistenceEvent<Slide>>,
+    bg_persistence: &mut EventWriter<PersistenceEvent<Background>>,
+    item_persistence: &mut EventWriter<PersistenceEvent<Item>>,
+    manifest_persistence: &mut EventWriter<PersistenceEvent<StoryManifest>>,
+) {
+    if file == Slide::file_name() {
+        slide_persistence.send(PersistenceEvent::Reload);
+    } else if file == Background::file_name() {
+        bg_persistence.send(PersistenceEvent::Reload);
+    } else if file == Item::file_name() {
+        item_persistence.send(PersistenceEvent::Reload);
+    } else if file == StoryManifest::file_name() {
+        manifest_persistence.send(PersistenceEvent::Reload);
+    }
+}
+
+/// Reloads files changed outside the editor, unless that would overwrite
+/// unsaved changes, then it asks first.
+fn watch(
+    time: Res<Time>,
+    mut watcher: ResMut<Watcher>,
+    project: Res<Project>,
+    mut disk_times: ResMut<DiskTimes>,
+    unsaved: Res<UnsavedChanges>,
+    conflict: Option<ResMut<ReloadConflict>>,
+    mut slide_persistence: EventWriter<PersistenceEvent<Slide>>,
+    mut bg_persistence: EventWriter<PersistenceEvent<Background>>,
+    mut item_persistence: EventWriter<PersistenceEvent<Item>>,
+    mut manifest_persistence: EventWriter<PersistenceEvent<StoryManifest>>,
+    mut commands: Commands,
+) {
+    if !watcher.0.tick(time.delta()).just_finished() {
+        return;
+    }
+    let mut conflicts = vec![];
+    let files = [
+        Slide::file_name(),
+        Background::file_name(),
+        Item::file_name(),
+        StoryManifest::file_name(),
+    ];
+    for file in files.iter() {
+        let path = project.path(file);
+        // A file gone missing is most likely being saved by another program,
+        // it is looked at again once it is back.
+        let modified = match modified(&path) {
+            None => continue,
+            Some(m) => m,
+        };
+        if disk_times.0.get(&path) == Some(&modified) {
+            continue;
+        }
+        info!("{} changed on disk", path.display());
+        disk_times.0.insert(path, modified);
+        if unsaved.contains(file) {
+            conflicts.push(*file);
+        } else {
+            reload(
+                file,
+                &mut slide_persistence,
+                &mut bg_persistence,
+                &mut item_persistence,
+                &mut manifest_persistence,
+            );
+        }
+    }
+    if conflicts.is_empty() {
+        return;
+    }
+    match conflict {
+        Some(mut c) => {
+            for f in conflicts {
+                if !c.0.contains(&f) {
+                    c.0.push(f);
+                }
+            }
+        }
+        None => commands.insert_resource(ReloadConflict(conflicts)),
+    }
+}
+
+/// Files changed on disk while the editor has unsaved changes to them.
+struct ReloadConflict(Vec<&'static str>);
+
+impl ReloadConflict {
+    fn render(
+        egui_context: ResMut<EguiContext>,
+        conflict: Option<Res<Self>>,
+        mut slide_persistence: EventWriter<PersistenceEvent<Slide>>,
+        mut bg_persistence: EventWriter<PersistenceEvent<Background>>,
+        mut item_persistence: EventWriter<PersistenceEvent<Item>>,
+        mut manifest_persistence: EventWriter<PersistenceEvent<StoryManifest>>,
+        mut commands: Commands,
+    ) {
+        let conflict = match conflict {
+            None => return,
+            Some(c) => c,
+        };
+        egui::Window::new("Changed on disk").show(egui_context.ctx(), |ui| {
+            ui.label("These files were changed outside the editor:");
+            for f in conflict.0.iter() {
+                ui.label(*f);
+            }
+            ui.label("They also have unsaved changes here.");
+            ui.horizontal(|ui| {
+                if ui.button("Reload from disk").clicked() {
+                    for f in conflict.0.iter() {
+                        reload(
+                            f,
+                            &mut slide_persistence,
+                            &mut bg_persistence,
+                            &mut item_persistence,
+                            &mut manifest_persistence,
+                        );
+                    }
+                    commands.remove_resource::<Self>();
+                }
+                if ui.button("Keep editor changes").clicked() {
+                    commands.remove_resource::<Self>();
+                }
+            });
+            ui.label("If the editor changes are kept, the next File Out overwrites the files.");
+        });
+    }
+}